@@ -1,13 +1,87 @@
 use std::env;
 use std::fs;
+use std::process;
+use mlisp::eval::EvalResult;
+use mlisp::formatter::{format_source, FormatOptions};
 use mlisp::interpreter::run_interpreter;
 
+const USAGE: &str = "Usage: mlisp <file>\n       mlisp fmt [--check] [--width <columns>] <file>...";
+
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    assert!(args.len() > 1, "Must supply a file path.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None => usage_error("Must supply a file path."),
+        Some("fmt") => process::exit(fmt(&args[1..])),
+        Some(path) => run(path),
+    }
+}
+
+fn usage_error(msg: &str) -> ! {
+    eprintln!("{}\n{}", msg, USAGE);
+    process::exit(2);
+}
 
-    let content = fs::read_to_string(&args[1])
+fn run(path: &str) {
+    let content = fs::read_to_string(path)
     	.expect("There was an error reading the file.");
 
-    run_interpreter(&content);
+    if let EvalResult::Err(err) = run_interpreter(&content) {
+        for line in err.lines() {
+            eprintln!("{}: {}", path, line);
+        }
+        process::exit(1);
+    }
+}
+
+/// Formats each file in place, or with `--check` only reports the files that aren't formatted.
+/// Returns the exit code: 1 if any file couldn't be formatted or, with `--check`, isn't formatted.
+fn fmt(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut options = FormatOptions::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => match args.next().and_then(|w| w.parse().ok()) {
+                Some(width) => options.line_width = width,
+                None => usage_error("--width must be followed by a number of columns."),
+            },
+            flag if flag.starts_with("--") => usage_error(&format!("Unknown option {}.", flag)),
+            path => paths.push(path),
+        }
+    }
+    if paths.is_empty() {
+        usage_error("Must supply a file path.");
+    }
+
+    let mut code = 0;
+    for path in paths {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                code = 1;
+                continue;
+            }
+        };
+        match format_source(&content, options) {
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                code = 1;
+            }
+            Ok(formatted) if formatted == content => {}
+            Ok(_) if check => {
+                println!("{}: not formatted", path);
+                code = 1;
+            }
+            Ok(formatted) => {
+                if let Err(err) = fs::write(path, formatted) {
+                    eprintln!("{}: {}", path, err);
+                    code = 1;
+                }
+            }
+        }
+    }
+    code
 }
@@ -0,0 +1,358 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Shl, Sub};
+
+/// An arbitrary-precision integer: a sign and a magnitude in base 2^32, least significant limb
+/// first. The magnitude never has trailing zero limbs, and zero is never negative, so equal
+/// values always have equal representations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt { negative: false, magnitude: Vec::new() }
+    }
+
+    pub fn from_i64(n: i64) -> BigInt {
+        let abs = n.unsigned_abs();
+        BigInt::new(n < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        BigInt { negative: negative && !magnitude.is_empty(), magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> BigInt {
+        BigInt::new(false, self.magnitude.clone())
+    }
+
+    /// The number of bits in the magnitude, not counting leading zeros.
+    pub fn bits(&self) -> usize {
+        self.magnitude.last().map_or(0, |top| self.magnitude.len() * 32 - top.leading_zeros() as usize)
+    }
+
+    /// The greatest common divisor of the magnitudes, which is zero only if both are zero.
+    pub fn gcd(&self, other: &BigInt) -> BigInt {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while let Some((_, r)) = a.div_rem(&b) {
+            a = b;
+            b = r;
+        }
+        a
+    }
+
+    /// Returns the value as an `i64`, or `None` if it is out of range.
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let abs = self.magnitude.iter().rev().fold(0u64, |acc, &limb| acc << 32 | limb as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(abs)
+        } else {
+            i64::try_from(abs).ok()
+        }
+    }
+
+    /// Returns the nearest `f64`, or an infinity if the value is beyond the range of `f64`.
+    pub fn to_f64(&self) -> f64 {
+        // Going through the decimal digits gets the rounding right without reimplementing it.
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Parses an optionally signed run of digits in the given radix. Returns `None` if there are
+    /// no digits or any character is not a digit.
+    pub fn parse(s: &str, radix: u32) -> Option<BigInt> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for c in digits.chars() {
+            mul_add_small(&mut magnitude, radix, c.to_digit(radix)?);
+        }
+        Some(BigInt::new(negative, magnitude))
+    }
+
+    /// Divides, truncating toward zero, so the remainder has the sign of the dividend. Returns
+    /// `None` if `other` is zero.
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = div_rem_magnitude(&self.magnitude, &other.magnitude);
+        Some((BigInt::new(self.negative != other.negative, q), BigInt::new(self.negative, r)))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitude(&self.magnitude, &other.magnitude));
+        }
+        // Opposite signs: subtract the smaller magnitude from the larger and keep its sign.
+        match cmp_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitude(&other.magnitude, &self.magnitude)),
+            _ => BigInt::new(self.negative, sub_magnitude(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::new(self.negative != other.negative, mul_magnitude(&self.magnitude, &other.magnitude))
+    }
+}
+
+impl Shl<usize> for &BigInt {
+    type Output = BigInt;
+
+    fn shl(self, bits: usize) -> BigInt {
+        let mut magnitude = vec![0u32; bits / 32];
+        let shift = bits % 32;
+        let mut carry = 0u32;
+        for &limb in &self.magnitude {
+            magnitude.push(if shift == 0 { limb } else { limb << shift | carry });
+            carry = if shift == 0 { 0 } else { limb >> (32 - shift) };
+        }
+        magnitude.push(carry);
+        BigInt::new(self.negative, magnitude)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Peel off nine decimal digits at a time, least significant chunk first.
+        const CHUNK: u32 = 1_000_000_000;
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_rem_small(&mut magnitude, CHUNK));
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", if self.negative { "-" } else { "" })?;
+        if let Some(first) = chunks.next() {
+            write!(f, "{}", first)?;
+        }
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, &limb) in long.iter().enumerate() {
+        let total = limb as u64 + *short.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push(total as u32);
+        carry = total >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// Subtracts `b` from `a`, which must have the larger or equal magnitude.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut total = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if total < 0 {
+            total += 1 << 32;
+            borrow = 1;
+        }
+        difference.push(total as u32);
+    }
+    difference
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &y) in b.iter().enumerate() {
+            let total = product[i + j] as u64 + x as u64 * y as u64 + carry;
+            product[i + j] = total as u32;
+            carry = total >> 32;
+        }
+        product[i + b.len()] = carry as u32;
+    }
+    product
+}
+
+/// Sets `magnitude` to `magnitude * factor + addend`.
+fn mul_add_small(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for limb in magnitude.iter_mut() {
+        let total = *limb as u64 * factor as u64 + carry;
+        *limb = total as u32;
+        carry = total >> 32;
+    }
+    if carry > 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+/// Divides `magnitude` in place by a non-zero `divisor`, returning the remainder.
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let current = remainder << 32 | *limb as u64;
+        *limb = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    remainder as u32
+}
+
+/// Long division of magnitudes, one bit of the quotient at a time. `b` must be non-zero.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = b {
+        let mut quotient = a.to_vec();
+        let remainder = div_rem_small(&mut quotient, *divisor);
+        return (quotient, vec![remainder]);
+    }
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        // remainder = remainder * 2 + the next bit of a
+        mul_add_small(&mut remainder, 2, a[bit / 32] >> (bit % 32) & 1);
+        if cmp_magnitude(&remainder, b) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, b);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> BigInt {
+        BigInt::parse(s, 10).unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints_decimal() {
+        for s in ["0", "1", "-1", "4294967296", "-9223372036854775809", "123456789012345678901234567890"] {
+            assert_eq!(s, big(s).to_string());
+        }
+        assert_eq!("0", big("-0").to_string());
+        assert_eq!(big("255"), BigInt::parse("ff", 16).unwrap());
+        assert_eq!(None, BigInt::parse("12a", 10));
+        assert_eq!(None, BigInt::parse("-", 10));
+    }
+
+    #[test]
+    fn converts_to_and_from_i64() {
+        for n in [0, 1, -1, i64::MAX, i64::MIN, 1 << 32] {
+            assert_eq!(Some(n), BigInt::from_i64(n).to_i64());
+        }
+        assert_eq!(None, big("9223372036854775808").to_i64());
+        assert_eq!(None, big("-9223372036854775809").to_i64());
+        assert_eq!(1e30, big("1000000000000000000000000000000").to_f64());
+    }
+
+    #[test]
+    fn arithmetic_handles_signs_and_carries() {
+        let a = big("18446744073709551616");
+        let b = big("-1");
+        assert_eq!(big("18446744073709551615"), &a + &b);
+        assert_eq!(big("18446744073709551617"), &a - &b);
+        assert_eq!(big("-18446744073709551616"), &a * &b);
+        assert_eq!(big("340282366920938463463374607431768211456"), &a * &a);
+        assert_eq!(BigInt::zero(), &a - &a);
+        assert!(big("-5") < big("3") && big("-5") < big("-3") && a > big("3"));
+        assert_eq!(big("-36893488147419103232"), &big("-1") << 65);
+        assert_eq!(66, (&big("-1") << 65).bits());
+    }
+
+    #[test]
+    fn gcd_of_magnitudes() {
+        assert_eq!(big("6"), big("-12").gcd(&big("18")));
+        assert_eq!(big("18446744073709551616"), big("36893488147419103232").gcd(&big("-18446744073709551616")));
+        assert_eq!(big("5"), big("5").gcd(&BigInt::zero()));
+        assert_eq!(BigInt::zero(), BigInt::zero().gcd(&BigInt::zero()));
+    }
+
+    #[test]
+    fn division_truncates_toward_zero() {
+        let n = big("340282366920938463463374607431768211457");
+        let d = big("-18446744073709551616");
+        assert_eq!(Some((big("-18446744073709551616"), big("1"))), n.div_rem(&d));
+        assert_eq!(Some((big("-3"), big("-1"))), big("-7").div_rem(&big("2")));
+        assert_eq!(None, n.div_rem(&BigInt::zero()));
+    }
+}
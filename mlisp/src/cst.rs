@@ -0,0 +1,347 @@
+use crate::lex::{lex, LexError, Token, TokenKind};
+use crate::parse::{closer, is_closer, mismatched, parse_program, unclosed, unexpected, ParseError};
+use crate::span::{Pos, Span};
+use crate::types::Expr;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+/// A lossless syntax tree: every byte of the source, including whitespace and comments, is kept
+/// and printing the tree gives back the source exactly. Use `lower` to get the `Expr`s that
+/// `parse_program` would produce for the same source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree<'a> {
+    pub nodes: Vec<Node<'a>>,
+    /// Trivia after the last token.
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// A `;` comment, up to but not including the end of the line.
+    LineComment,
+    /// A (possibly nested) `#| ... |#` comment.
+    BlockComment,
+}
+
+/// Source text that is not part of any token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// A token along with its exact source text and the trivia just before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken<'a> {
+    pub token: Token<'a>,
+    pub text: &'a str,
+    pub leading: Vec<Trivia<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node<'a> {
+    /// A literal, string or character.
+    Atom(SyntaxToken<'a>),
+    /// A list, vector or map, with the delimiters around its children.
+    Seq {
+        open: SyntaxToken<'a>,
+        children: Vec<Node<'a>>,
+        close: SyntaxToken<'a>,
+    },
+    /// A reader shorthand such as `'`, or a `#;` datum comment, applied to the node after it.
+    /// `comments` holds any datum comments between the two, which the prefix skips over.
+    Prefixed {
+        prefix: SyntaxToken<'a>,
+        comments: Vec<Node<'a>>,
+        node: Box<Node<'a>>,
+    },
+}
+
+#[derive(Debug)]
+pub enum CstError {
+    Lex(LexError),
+    Parse(ParseError),
+}
+
+impl CstError {
+    /// The region of source the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            CstError::Lex(err) => err.span(),
+            CstError::Parse(err) => err.span(),
+        }
+    }
+}
+
+impl fmt::Display for CstError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CstError::Lex(err) => write!(f, "{}", err),
+            CstError::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<'a> SyntaxTree<'a> {
+    /// Every token in the tree, in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken<'a>> {
+        let mut tokens = Vec::new();
+        for node in &self.nodes {
+            node.collect_tokens(&mut tokens);
+        }
+        tokens
+    }
+
+    /// Converts the tree into the `Expr`s it denotes, dropping trivia and datum comments.
+    pub fn lower(&self) -> Result<Vec<Rc<Expr>>, ParseError> {
+        let tokens: Vec<Token> = self.tokens().into_iter().map(|t| t.token.clone()).collect();
+        parse_program(&tokens)
+    }
+}
+
+impl fmt::Display for SyntaxTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in self.tokens() {
+            token.fmt(f)?;
+        }
+        self.trailing.iter().try_for_each(|t| write!(f, "{}", t.text))
+    }
+}
+
+impl<'a> SyntaxToken<'a> {
+    pub fn span(&self) -> Span {
+        self.token.span
+    }
+}
+
+/// Writes the token's leading trivia followed by the token itself.
+impl fmt::Display for SyntaxToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for trivia in &self.leading {
+            write!(f, "{}", trivia.text)?;
+        }
+        write!(f, "{}", self.text)
+    }
+}
+
+impl<'a> Node<'a> {
+    pub fn first_token(&self) -> &SyntaxToken<'a> {
+        match self {
+            Node::Atom(token) | Node::Seq { open: token, .. } | Node::Prefixed { prefix: token, .. } => token,
+        }
+    }
+
+    pub fn last_token(&self) -> &SyntaxToken<'a> {
+        let mut node = self;
+        loop {
+            match node {
+                Node::Atom(token) | Node::Seq { close: token, .. } => return token,
+                Node::Prefixed { node: inner, .. } => node = inner,
+            }
+        }
+    }
+
+    /// The source covered by the node, from its first token to its last, excluding the trivia
+    /// before it.
+    pub fn span(&self) -> Span {
+        self.first_token().span().to(self.last_token().span())
+    }
+
+    /// Every token in the node, in source order.
+    pub fn tokens(&self) -> Vec<&SyntaxToken<'a>> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'n>(&'n self, tokens: &mut Vec<&'n SyntaxToken<'a>>) {
+        // Walked with an explicit stack, so deeply nested trees don't overflow the call stack.
+        let mut pending = vec![Walk::Node(self)];
+        while let Some(next) = pending.pop() {
+            match next {
+                Walk::Token(token) | Walk::Node(Node::Atom(token)) => tokens.push(token),
+                Walk::Node(Node::Seq { open, children, close }) => {
+                    tokens.push(open);
+                    pending.push(Walk::Token(close));
+                    pending.extend(children.iter().rev().map(Walk::Node));
+                }
+                Walk::Node(Node::Prefixed { prefix, comments, node }) => {
+                    tokens.push(prefix);
+                    pending.push(Walk::Node(node));
+                    pending.extend(comments.iter().rev().map(Walk::Node));
+                }
+            }
+        }
+    }
+
+    fn take_children(&mut self) -> Vec<Node<'a>> {
+        match self {
+            Node::Atom(_) => Vec::new(),
+            Node::Seq { children, .. } => mem::take(children),
+            Node::Prefixed { comments, node, .. } => {
+                let mut children = mem::take(comments);
+                children.append(&mut node.take_children());
+                children
+            }
+        }
+    }
+
+    fn is_datum_comment(&self) -> bool {
+        matches!(self, Node::Prefixed { prefix, .. } if prefix.token.kind == TokenKind::DatumComment)
+    }
+
+    /// Converts the node into the `Expr` it denotes, or `None` if it is a datum comment.
+    pub fn lower(&self) -> Result<Option<Rc<Expr>>, ParseError> {
+        let tokens: Vec<Token> = self.tokens().into_iter().map(|t| t.token.clone()).collect();
+        parse_program(&tokens).map(|exprs| exprs.into_iter().next())
+    }
+}
+
+/// Children are dropped with an explicit worklist rather than recursively, so that dropping deeply
+/// nested trees doesn't overflow the stack.
+impl Drop for Node<'_> {
+    fn drop(&mut self) {
+        let mut pending = self.take_children();
+        while let Some(mut node) = pending.pop() {
+            pending.append(&mut node.take_children());
+        }
+    }
+}
+
+/// What is left to visit while walking a node's tokens.
+enum Walk<'n, 'a> {
+    Node(&'n Node<'a>),
+    Token(&'n SyntaxToken<'a>),
+}
+
+/// Writes the node's source text, without the trivia before its first token.
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tokens = self.tokens();
+        write!(f, "{}", tokens[0].text)?;
+        tokens[1..].iter().try_for_each(|t| t.fmt(f))
+    }
+}
+
+/// Splits the text between two tokens, which the lexer has already checked, into trivia.
+fn split_trivia<'a>(source: &'a str, start: Pos, end: usize) -> Vec<Trivia<'a>> {
+    let mut trivia = Vec::new();
+    let mut pos = start;
+    while pos.offset < end {
+        let rest = &source[pos.offset..end];
+        let (kind, len) = if rest.starts_with(';') {
+            (TriviaKind::LineComment, rest.find('\n').unwrap_or(rest.len()))
+        } else if rest.starts_with("#|") {
+            (TriviaKind::BlockComment, block_comment_len(rest))
+        } else {
+            let len = rest.find(|c: char| !c.is_ascii_whitespace()).unwrap_or(rest.len());
+            (TriviaKind::Whitespace, len)
+        };
+        let text = &rest[..len];
+        let next = text.chars().fold(pos, Pos::advance);
+        trivia.push(Trivia { kind, text, span: Span::new(pos, next) });
+        pos = next;
+    }
+    trivia
+}
+
+/// The length of the nested block comment at the start of `text`.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if text[i..].starts_with("#|") {
+            depth += 1;
+            i += 2;
+        } else if text[i..].starts_with("|#") {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += text[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    text.len()
+}
+
+/// A sequence or prefix that is still waiting for the rest of its tokens. A prefix keeps the datum
+/// comments it has skipped so far.
+enum Frame<'a> {
+    Seq(SyntaxToken<'a>, Vec<Node<'a>>),
+    Prefix(SyntaxToken<'a>, Vec<Node<'a>>),
+}
+
+/// Parses `source` into a lossless syntax tree. Only the structure of delimiters and prefixes is
+/// checked here; problems such as a map literal with an odd number of forms are reported when the
+/// tree is lowered.
+pub fn parse_cst(source: &str) -> Result<SyntaxTree<'_>, CstError> {
+    let tokens = lex(source).map_err(CstError::Lex)?;
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut nodes = Vec::new();
+    let mut pos = Pos::start();
+
+    for token in tokens {
+        let leading = split_trivia(source, pos, token.span.start.offset);
+        pos = token.span.end;
+        let text = &source[token.span.range()];
+        let token = SyntaxToken { token, text, leading };
+
+        let mut node = match &token.token.kind {
+            TokenKind::LPar | TokenKind::LBracket | TokenKind::LBrace => {
+                stack.push(Frame::Seq(token, Vec::new()));
+                continue;
+            }
+            TokenKind::DatumComment
+            | TokenKind::Quote
+            | TokenKind::Quasiquote
+            | TokenKind::Unquote
+            | TokenKind::UnquoteSplicing => {
+                stack.push(Frame::Prefix(token, Vec::new()));
+                continue;
+            }
+            kind if is_closer(kind) => match stack.pop() {
+                Some(Frame::Seq(open, children)) if closer(&open.token.kind).as_ref() == Some(kind) => {
+                    Node::Seq { open, children, close: token }
+                }
+                Some(Frame::Seq(open, _)) => return Err(CstError::Parse(mismatched(&open.token, &token.token))),
+                _ => return Err(CstError::Parse(unexpected(&token.token))),
+            },
+            _ => Node::Atom(token),
+        };
+
+        // Hand the finished node to whatever is waiting on it.
+        loop {
+            match stack.pop() {
+                None => {
+                    nodes.push(node);
+                    break;
+                }
+                Some(Frame::Seq(open, mut children)) => {
+                    children.push(node);
+                    stack.push(Frame::Seq(open, children));
+                    break;
+                }
+                // A datum comment is skipped over, as the parser does, rather than being prefixed.
+                Some(Frame::Prefix(prefix, mut comments)) if node.is_datum_comment() => {
+                    comments.push(node);
+                    stack.push(Frame::Prefix(prefix, comments));
+                    break;
+                }
+                Some(Frame::Prefix(prefix, comments)) => {
+                    node = Node::Prefixed { prefix, comments, node: Box::new(node) }
+                }
+            }
+        }
+    }
+
+    match stack.last() {
+        Some(Frame::Seq(open, _)) => Err(CstError::Parse(unclosed(&open.token))),
+        Some(Frame::Prefix(..)) => Err(CstError::Parse(ParseError::EOF(Span::point(pos)))),
+        None => Ok(SyntaxTree { nodes, trailing: split_trivia(source, pos, source.len()) }),
+    }
+}
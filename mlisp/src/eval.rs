@@ -1,5 +1,9 @@
-use crate::types::Expr;
-use std::collections::HashMap;
+use crate::number::Number;
+use crate::rational::Rational;
+use crate::types::{Arity, Expr, PrintStyle};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::rc::Rc;
 
 #[derive(Debug, PartialEq)]
@@ -9,9 +13,122 @@ pub enum EvalResult {
     Unit,
 }
 
+/// A single scope, mapping names to their values. Functions are values too. Contexts are shared
+/// with the functions made in them, so a function sees definitions and `set!`s made there later.
+pub type Context = Rc<RefCell<HashMap<String, Rc<Expr>>>>;
+
+/// The contexts in scope at the point being evaluated, innermost last. Calling a function replaces
+/// them with the contexts the function was made in, plus one for its arguments.
 #[derive(Debug)]
 pub struct Environment {
-    pub contexts: Vec<HashMap<String, (Vec<String>, Rc<Expr>)>>,
+    pub contexts: Vec<Context>,
+}
+
+/// A function holds the contexts it was made in, which usually hold the function, so reference
+/// counting alone never frees them. Dropping an environment finds every context and value it can
+/// reach, and empties the contexts that are only referenced from among those, which breaks the
+/// cycles. Anything still referenced from outside, such as a closure handed back to the host, is
+/// kept along with everything it reaches.
+impl Drop for Environment {
+    fn drop(&mut self) {
+        // Each reached context and value is held once here, and counted once for every reference
+        // to it from another reached context or value.
+        let mut contexts: HashMap<*const (), Context> = HashMap::new();
+        let mut values: HashMap<*const (), Rc<Expr>> = HashMap::new();
+        let mut inner_refs: HashMap<*const (), usize> = HashMap::new();
+        let mut pending_contexts = mem::take(&mut self.contexts);
+        let mut pending_values: Vec<Rc<Expr>> = Vec::new();
+        loop {
+            while let Some(ctx) = pending_contexts.pop() {
+                let ptr = Rc::as_ptr(&ctx) as *const ();
+                if contexts.contains_key(&ptr) {
+                    continue;
+                }
+                for value in ctx.borrow().values() {
+                    *inner_refs.entry(Rc::as_ptr(value) as *const ()).or_insert(0) += 1;
+                    pending_values.push(value.clone());
+                }
+                contexts.insert(ptr, ctx);
+            }
+            let value = match pending_values.pop() {
+                Some(value) => value,
+                None => break,
+            };
+            let ptr = Rc::as_ptr(&value) as *const ();
+            if values.contains_key(&ptr) {
+                continue;
+            }
+            let (envs, children) = references(&value);
+            for ctx in envs {
+                *inner_refs.entry(Rc::as_ptr(&ctx) as *const ()).or_insert(0) += 1;
+                pending_contexts.push(ctx);
+            }
+            for child in children {
+                *inner_refs.entry(Rc::as_ptr(&child) as *const ()).or_insert(0) += 1;
+                pending_values.push(child);
+            }
+            values.insert(ptr, value);
+        }
+
+        // Anything with more references than the reached ones plus ours is used elsewhere.
+        let inner = |ptr: &*const ()| inner_refs.get(ptr).copied().unwrap_or(0);
+        let mut live_contexts: Vec<Context> = contexts.iter()
+            .filter(|(ptr, ctx)| Rc::strong_count(ctx) > inner(ptr) + 1)
+            .map(|(_, ctx)| ctx.clone())
+            .collect();
+        let mut live_values: Vec<Rc<Expr>> = values.iter()
+            .filter(|(ptr, value)| Rc::strong_count(value) > inner(ptr) + 1)
+            .map(|(_, value)| value.clone())
+            .collect();
+        let mut live = HashSet::new();
+        loop {
+            while let Some(ctx) = live_contexts.pop() {
+                if live.insert(Rc::as_ptr(&ctx) as *const ()) {
+                    live_values.extend(ctx.borrow().values().cloned());
+                }
+            }
+            let value = match live_values.pop() {
+                Some(value) => value,
+                None => break,
+            };
+            if live.insert(Rc::as_ptr(&value) as *const ()) {
+                let (envs, children) = references(&value);
+                live_contexts.extend(envs);
+                live_values.extend(children);
+            }
+        }
+
+        // Every reached context is kept alive by `contexts` until they have all been emptied.
+        for (ptr, ctx) in &contexts {
+            if !live.contains(ptr) {
+                ctx.borrow_mut().clear();
+            }
+        }
+    }
+}
+
+/// The contexts and values that `value` holds directly.
+fn references(value: &Expr) -> (Vec<Context>, Vec<Rc<Expr>>) {
+    match value {
+        Expr::Lambda(f) => (f.env.clone(), vec![f.body.clone()]),
+        Expr::List(xs) | Expr::Vector(xs) => (Vec::new(), xs.clone()),
+        Expr::DottedList(xs, tail) => (Vec::new(), xs.iter().chain(Some(tail)).cloned().collect()),
+        Expr::Map(kvs) => (Vec::new(), kvs.iter().flat_map(|(k, v)| [k.clone(), v.clone()]).collect()),
+        _ => (Vec::new(), Vec::new()),
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Environment {
+        let defaults: HashMap<String, Rc<Expr>> = [
+            ("False".into(), Expr::boolean(false)),
+            ("True".into(), Expr::boolean(true)),
+        ].iter().cloned().collect();
+
+        Environment{
+            contexts: vec![Rc::new(RefCell::new(defaults))],
+        }
+    }
 }
 
 impl Environment {
@@ -30,40 +147,27 @@ impl Environment {
         env
     }
 
-    pub fn default() -> Environment {
-        let defaults: HashMap<String, (Vec<String>, Rc<Expr>)> = [
-            ("False".into(), (Vec::new(), Expr::list(&[]))),
-            ("True".into(), (Vec::new(), Expr::list(&[Expr::fnum(1.0)]))),
-        ].iter().cloned().collect();
-
-        Environment{
-            contexts: vec![defaults],
-        }
-    }
-
-    /// Looks up the given symbol in the Environment.
-    pub fn lookup(&self, symbol: &str) -> Option<(Vec<String>, Rc<Expr>)> {
+    /// Looks up the given symbol in the Environment, and then among the builtins.
+    pub fn lookup(&self, symbol: &str) -> Option<Rc<Expr>> {
         self.contexts
             .iter()
             .rev()
-            .find(|ctx| ctx.contains_key(symbol))
-            .map(|ctx| ctx.get(symbol))
-            .flatten()
-            .cloned()
+            .find_map(|ctx| ctx.borrow().get(symbol).cloned())
+            .or_else(|| BUILTINS.with(|builtins| builtins.borrow().get(symbol).cloned()))
     }
 
-    /// Checks whether the given symbol exists in the Environment.
+    /// Checks whether the given symbol exists in the Environment or among the builtins.
     pub fn contains_key(&self, symbol: &str) -> bool {
         self.contexts
             .iter()
             .rev()
-            .find(|ctx| ctx.contains_key(symbol))
-            .is_some()
+            .any(|ctx| ctx.borrow().contains_key(symbol))
+            || BUILTINS.with(|builtins| builtins.borrow().contains_key(symbol))
     }
 
     /// Pushes a new context on the `contexts` stack.
     pub fn push_context(&mut self) {
-        self.contexts.push(HashMap::new());
+        self.contexts.push(Rc::new(RefCell::new(HashMap::new())));
     }
 
     /// Pops the last context from the `contexts` stack.
@@ -77,20 +181,41 @@ impl Environment {
             .last_mut()
             .map_or_else(
                 || Err("Environment does not have any context to add to.".into()),
-                |ctx| { ctx.insert(var.to_string(), (Vec:: new(), val.clone())); Ok(()) },
+                |ctx| { ctx.borrow_mut().insert(var.to_string(), val.clone()); Ok(()) },
+            )
+    }
+
+    /// Changes the value of an existing variable in the innermost context that defines it.
+    pub fn set_var(&mut self, var: &str, val: Rc<Expr>) -> Result<(), String> {
+        self.contexts
+            .iter()
+            .rev()
+            .find(|ctx| ctx.borrow().contains_key(var))
+            .map_or_else(
+                || Err(format!("Cannot set! {}, which is not defined.", var)),
+                |ctx| { ctx.borrow_mut().insert(var.to_string(), val); Ok(()) },
             )
     }
 
-    /// Adds a function definition to the Environment
+    /// Adds a function definition to the Environment, binding `name` to a function value just as
+    /// `(fn name (params) body)` does.
     pub fn add_fn(&mut self, name: &str, params: &[String], body: Rc<Expr>) -> Result<(), String> {
-        self.contexts.last_mut().map_or(
-            Err("Environment does not have a context to add to.".into()),
-            |ctx| {
-                let param_names: Vec<String> = params.iter().map(|s| s.to_string()).collect();
-                ctx.insert(name.to_string(), (param_names, body.clone()));
-                Ok(())
-            }
-        )
+        let f = Expr::lambda(params, None, body, &self.contexts);
+        self.add_var(name, f)
+    }
+
+    /// Binds `name` in the outermost context, creating that context if there are none.
+    pub fn add_global(&mut self, name: &str, val: Rc<Expr>) {
+        if self.contexts.is_empty() {
+            self.push_context();
+        }
+        self.contexts[0].borrow_mut().insert(name.to_string(), val);
+    }
+
+    /// Binds `name` in the outermost context to a function implemented in Rust. `func` is given
+    /// the evaluated arguments once the call has been checked against `arity`.
+    pub fn register_native(&mut self, name: &str, arity: Arity, func: impl Fn(&[Rc<Expr>]) -> EvalResult + 'static) {
+        self.add_global(name, Expr::native(name, arity, func));
     }
 
     pub fn num_contexts(&self) -> usize {
@@ -98,78 +223,64 @@ impl Environment {
     }
 }
 
-/// Generates the output printed to standard out when the user calls print.
-pub fn gen_print_output(expr: Rc<Expr>, env: &mut Environment) -> String {
-    match &*expr{
-        Expr::Symbol(s) => {
-            match env.lookup(&s){
-                None => s.to_string(),
-                Some((params, e)) if params.len() == 0 => gen_print_output(e,env),
-                _ => format!("<func-object: {}>", s.to_string()),
-            }
-        },
-        Expr::FNum(n) => format!("{}",n),
-        Expr::List(vals) => {
-            let vals_out: Vec<String> = vals.iter()
-                .cloned()
-                .map(|x| gen_print_output(x, env))
-                .collect();
-            format!("({})", vals_out.join(" "))
-        }
-    }
+/// Generates the output printed to standard out when the user calls print: the already evaluated
+/// values in `PrintStyle::Display`, separated by spaces.
+pub fn gen_print_output(vals: &[Rc<Expr>]) -> String {
+    let output: Vec<String> = vals.iter()
+        .map(|val| val.printed(PrintStyle::Display).to_string())
+        .collect();
+    output.join(" ")
 }
 
-fn evaluate_symbol(expr: Rc<Expr>, sym: &str, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    env.lookup(sym).map_or_else(
-            || EvalResult::Expr(expr),
-            |(param_names, expression)| {
-                if param_names.is_empty() {
-                    println!("Evaluating {:?}, lookup returned {:?}", sym, expression);
-                    eval(expression.clone(), env)
-                } else {
-                    if args.len() != param_names.len() {
-                        return EvalResult::Err(format!("Provided {} arguments but expected {}",
-                            args.len(),
-                            param_names.len()
-                        ));
-                    }
-                    let mapped_args: Result<Vec<(String, Rc<Expr>)>, String> = args
-                        .iter()
-                        .zip(param_names)
-                        .map(|(expr, name)| match eval(expr.clone(), env) {
-                            EvalResult::Expr(e) => Ok((name.to_string(), e.clone())),
-                            EvalResult::Err(err) => Err(err),
-                            _ => Err("Cannot pass Unit as an argument to a function.".into()),
-                        })
-                        .collect();
-                    env.push_context();
-
-                    let result = mapped_args.map_or_else(
-                        |e| EvalResult::Err(e),
-                        |args_tuples| {
-                            args_tuples.iter().for_each(|(name, expr)| {
-                                let _ = env.add_var(name, expr.clone());
-                            });
-                            eval(expression.clone(), env)
-                        },
-                    );
-                    env.pop_context();
-                    result
-                }
+/// Evaluates the arguments of a function call in the caller's contexts.
+fn eval_args(args: &[Rc<Expr>], env: &mut Environment) -> Result<Vec<Rc<Expr>>, String> {
+    args.iter()
+        .map(|expr| match eval(expr.clone(), env) {
+            EvalResult::Expr(e) => Ok(e),
+            EvalResult::Err(err) => Err(err),
+            EvalResult::Unit => Err("Cannot pass Unit as an argument to a function.".into()),
+        })
+        .collect()
+}
+
+/// Calls the function `f` with the already evaluated `args`. A `lambda`'s body is evaluated in
+/// the contexts it was made in, so it can't see the caller's variables.
+pub fn apply(f: &Expr, args: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    match f {
+        Expr::Lambda(f) => {
+            if let Err(err) = f.arity().check(args.len()) {
+                return EvalResult::Err(err);
             }
-    )
+            let callers = mem::replace(&mut env.contexts, f.env.clone());
+            env.push_context();
+            f.params.iter().zip(args.iter()).for_each(|(name, val)| {
+                let _ = env.add_var(name, val.clone());
+            });
+            if let Some(rest) = &f.rest {
+                let _ = env.add_var(rest, Expr::list(&args[f.params.len()..]));
+            }
+            let result = eval(f.body.clone(), env);
+            env.contexts = callers;
+            result
+        },
+        Expr::Native(f) => match f.arity.check(args.len()) {
+            Ok(()) => (f.func)(args),
+            Err(err) => EvalResult::Err(err),
+        },
+        _ => EvalResult::Err(format!("{} is not a function.", f)),
+    }
 }
 
 fn add_var_to_env(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult{
     if vals.len() != 2 {
-        EvalResult::Err("Invalid variable definition. Should look like (let someVar someExpr)".into());
+        return EvalResult::Err("Invalid variable definition. Should look like (let someVar someExpr)".into());
     }
 
     match (&*vals[0], &vals[1]){
         (Expr::Symbol(s), e) => match eval(e.clone(),env){
             EvalResult::Expr(e) => env
                 .add_var(s,e)
-                .map_or_else(|s| EvalResult::Err(s), |_| EvalResult::Unit),
+                .map_or_else(EvalResult::Err, |_| EvalResult::Unit),
             EvalResult::Unit => EvalResult::Err("Cannot assign unit to a variable.".into()),
             err => err,
 
@@ -180,363 +291,598 @@ fn add_var_to_env(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult{
     }
 }
 
-fn add_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty() {
-        return EvalResult::Err("Must perform addition on at least one number".into());
+fn set_var(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 2 {
+        return EvalResult::Err("Invalid assignment. Should look like (set! someVar someExpr)".into());
+    }
+    match &*vals[0] {
+        Expr::Symbol(s) => match eval(vals[1].clone(), env) {
+            EvalResult::Expr(e) => env.set_var(s, e).map_or_else(EvalResult::Err, |_| EvalResult::Unit),
+            EvalResult::Unit => EvalResult::Err("Cannot assign unit to a variable.".into()),
+            err => err,
+        },
+        _ => EvalResult::Err("The variable in an assignment must be a symbol.".into()),
     }
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(),env) {
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::FNum(n) => Ok(*n),
-                _ => Err("Can only sum numbers.".into()),
-            },
-            _ => Err("Can only sum numbers.".into()),
-        })
-        .collect::<Result<Vec<f64>, String>>();
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| EvalResult::Expr(Expr::fnum(xs.iter().sum())),
-    )
 }
 
-fn sub_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty() {
-        return EvalResult::Err("Must perform subtraction on at least one number".into());
+/// Evaluates each expression in order, returning the value of the last.
+fn begin(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    let mut result = EvalResult::Unit;
+    for val in vals {
+        result = eval(val.clone(), env);
+        if let EvalResult::Err(_) = result {
+            break;
+        }
     }
+    result
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(),env) {
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::FNum(n) => Ok(*n),
-                _ => Err("Can only subtract numbers.".into()),
-            },
-            _ => Err("Can only subtract numbers.".into()),
-        })
-        .collect::<Result<Vec<f64>, String>>();
+fn add_vals(vals: &[Rc<Expr>]) -> EvalResult {
+    numbers(vals, "Can only sum numbers.").map_or_else(
+        EvalResult::Err,
+        |xs| fold_numbers(xs, i64::checked_add, |a, b| Ok(Number::exact(a + b)), |a, b| a + b),
+    )
+}
 
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| if xs.len() == 1 {
-            EvalResult::Expr(Expr::fnum(xs.iter().fold(0.0, |mut acc, &x| {acc -= x; acc})))
-        } else {
-            EvalResult::Expr(Expr::fnum(xs.iter().skip(1).fold(xs[0], |mut acc, &x| {acc -= x; acc})))
-        },
+fn sub_vals(vals: &[Rc<Expr>]) -> EvalResult {
+    numbers(vals, "Can only subtract numbers.").map_or_else(
+        EvalResult::Err,
+        |xs| fold_numbers(apply_single_to(0, xs), i64::checked_sub, |a, b| Ok(Number::exact(a - b)), |a, b| a - b),
     )
 }
 
-fn mul_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty(){
-        return EvalResult::Err("Must perform multiplication on at least one number".into());
-    }
+fn mul_vals(vals: &[Rc<Expr>]) -> EvalResult {
+    numbers(vals, "Can only mutiply numbers.").map_or_else(
+        EvalResult::Err,
+        |xs| fold_numbers(xs, i64::checked_mul, |a, b| Ok(Number::exact(a * b)), |a, b| a * b),
+    )
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::FNum(n) => Ok(*n),
-                _ => Err("Can only mutiply numbers.".into()),
-            },
-            _ => Err("Can only mutiply numbers.".into()),
-        })
-        .collect::<Result<Vec<f64>, String>>();
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| EvalResult::Expr(Expr::fnum(xs.iter().product())),
+/// Dividing exact numbers gives an exact result, a fraction if the divisor doesn't divide evenly.
+/// Dividing an exact number by zero is an error; dividing a float by zero gives an infinity. A
+/// single argument gives its reciprocal, so `(/ 3)` is `1/3`.
+fn div_vals(vals: &[Rc<Expr>]) -> EvalResult {
+    let small_div = |a: i64, b: i64| if b != 0 && a.wrapping_rem(b) == 0 { a.checked_div(b) } else { None };
+    let exact_div = |a: &Rational, b: &Rational| {
+        a.checked_div(b).map(Number::exact).ok_or_else(|| "Division by zero.".to_string())
+    };
+    numbers(vals, "Can only divide numbers.").map_or_else(
+        EvalResult::Err,
+        |xs| fold_numbers(apply_single_to(1, xs), small_div, exact_div, |a, b| a / b),
     )
 }
 
-fn div_vals(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty(){
-        return EvalResult::Err("Must perform division on at least one number".into());
+fn as_number(exp: &Expr) -> Option<Number> {
+    match exp {
+        Expr::Int(n) => Some(Number::Int(*n)),
+        Expr::BigInt(n) => Some(Number::Big(n.clone())),
+        Expr::Rational(r) => Some(Number::Rational(r.clone())),
+        Expr::FNum(n) => Some(Number::Float(*n)),
+        _ => None,
     }
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::FNum(n) => Ok(*n),
-                _ => Err("Can only divide numbers.".into()),
-            },
-            _ => Err("Can only divide numbers.".into()),
-        })
-        .collect::<Result<Vec<f64>, String>>();
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| if xs.len() == 1 {
-            EvalResult::Expr(Expr::fnum(xs.iter().fold(0.0, |mut acc, &x| {acc /= x; acc})))
-        } else {
-            EvalResult::Expr(Expr::fnum(xs.iter().skip(1).fold(xs[0], |mut acc, &x| {acc /= x; acc})))
-        },
-    )
+/// Converts the arguments of an arithmetic builtin, failing with `err` if any isn't a number.
+fn numbers(vals: &[Rc<Expr>], err: &str) -> Result<Vec<Number>, String> {
+    vals.iter()
+        .map(|exp| as_number(exp).ok_or_else(|| err.to_string()))
+        .collect()
 }
 
-fn or(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty(){
-        return EvalResult::Err("Must perform on at least one value".into());
+/// A single argument to `-` or `/` is applied to the operation's identity, as in `(- 0 x)` and
+/// `(/ 1 x)`.
+fn apply_single_to(identity: i64, mut xs: Vec<Number>) -> Vec<Number> {
+    if xs.len() == 1 {
+        xs.insert(0, Number::Int(identity));
     }
+    xs
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::Symbol(s) => if s == "True" {
-                    Ok("True".to_string())
-                } else {
-                    Ok("False".to_string())
-                },
-                Expr::List(vs) => if vs.len() == 0 {
-                    Ok("False".to_string())
-                } else {
-                    Ok("True".to_string())
-                },
-                _ => Err("Can only perform on boolean values.".into()),
-            },
-            _ => Err("Can only perform on boolean values.".into()),
-        })
-        .collect::<Result<Vec<String>, String>>();
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| if xs.iter().any(|x| x == "True") {
-            EvalResult::Expr(Expr::symbol("True"))
-        } else {
-            EvalResult::Expr(Expr::symbol("False"))
-        },
-    )
+/// Folds the numbers from left to right. Two exact numbers are combined exactly: with `small_op`
+/// while both are integers and it fits in an `i64`, and with `exact_op` once it returns `None` or
+/// either side is a bignum or fraction. Once either side is a float both are converted to floats
+/// and combined with `float_op`, so a float anywhere in the arguments makes the result a float.
+fn fold_numbers(
+    xs: Vec<Number>,
+    small_op: impl Fn(i64, i64) -> Option<i64>,
+    exact_op: impl Fn(&Rational, &Rational) -> Result<Number, String>,
+    float_op: fn(f64, f64) -> f64,
+) -> EvalResult {
+    let mut xs = xs.into_iter();
+    let first = xs.next().unwrap_or(Number::Int(0));
+    xs.try_fold(first, |acc, x| {
+        if let (Number::Int(a), Number::Int(b)) = (&acc, &x) {
+            if let Some(n) = small_op(*a, *b) {
+                return Ok(Number::Int(n));
+            }
+        }
+        match (acc.to_rational(), x.to_rational()) {
+            (Some(a), Some(b)) => exact_op(&a, &b),
+            _ => Ok(Number::Float(float_op(acc.to_f64(), x.to_f64()))),
+        }
+    })
+    .map_or_else(EvalResult::Err, |n| EvalResult::Expr(n.to_expr()))
 }
 
-fn and(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+/// The builtins that divide one integer by another.
+const INTEGER_DIVISION_OPS: &[&str] = &["quotient", "remainder", "modulo"];
+
+/// `quotient` truncates toward zero, `remainder` takes the sign of the dividend and `modulo` the
+/// sign of the divisor. Integral floats are accepted and give a float result.
+fn integer_division(op: &str, vals: &[Rc<Expr>]) -> EvalResult {
+    let err = format!("{} can only be applied to integers.", op);
+    let xs = match numbers(vals, &err) {
+        Ok(xs) => xs,
+        Err(err) => return EvalResult::Err(err),
+    };
+    let result = match (xs[0].to_bigint(), xs[1].to_bigint()) {
+        (Some(a), Some(b)) => a.div_rem(&b).ok_or_else(|| "Division by zero.".to_string()).map(|(q, r)| {
+            Number::integer(match op {
+                "quotient" => q,
+                "remainder" => r,
+                _ if !r.is_zero() && r.is_negative() != b.is_negative() => &r + &b,
+                _ => r,
+            })
+        }),
+        _ => {
+            let (a, b) = (xs[0].to_f64(), xs[1].to_f64());
+            if a.fract() != 0.0 || b.fract() != 0.0 {
+                Err(err)
+            } else if b == 0.0 {
+                Err("Division by zero.".to_string())
+            } else {
+                let r = a % b;
+                Ok(Number::Float(match op {
+                    "quotient" => (a / b).trunc(),
+                    "remainder" => r,
+                    _ if r != 0.0 && (r < 0.0) != (b < 0.0) => r + b,
+                    _ => r,
+                }))
+            }
+        }
+    };
+    result.map_or_else(EvalResult::Err, |n| EvalResult::Expr(n.to_expr()))
+}
+
+/// The builtins that take apart or convert a single number.
+const NUMBER_OPS: &[&str] = &["numerator", "denominator", "exact->inexact"];
+
+/// `numerator` and `denominator` give the parts of an exact number in lowest terms, where an
+/// integer is its own numerator over a denominator of one. `exact->inexact` converts any number to
+/// the nearest float.
+fn number_op(op: &str, vals: &[Rc<Expr>]) -> EvalResult {
+    let n = match numbers(vals, &format!("{} can only be applied to a number.", op)) {
+        Ok(mut xs) => xs.remove(0),
+        Err(err) => return EvalResult::Err(err),
+    };
+    match (op, n.to_rational()) {
+        ("exact->inexact", _) => EvalResult::Expr(Expr::fnum(n.to_f64())),
+        ("numerator", Some(r)) => EvalResult::Expr(Expr::integer(r.numerator().clone())),
+        ("denominator", Some(r)) => EvalResult::Expr(Expr::integer(r.denominator().clone())),
+        _ => EvalResult::Err(format!("{} can only be applied to an exact number.", op)),
+    }
+}
+
+/// Evaluates the condition of `if`, `and` or `or`, which is true unless it is `#f`; see
+/// `Expr::is_truthy`.
+fn condition(e: &Rc<Expr>, env: &mut Environment, unit_err: &str) -> Result<bool, EvalResult> {
+    match eval(e.clone(), env) {
+        EvalResult::Expr(exp) => Ok(exp.is_truthy()),
+        EvalResult::Unit => Err(EvalResult::Err(unit_err.into())),
+        err => Err(err),
+    }
+}
+
+/// `or` and `and` evaluate their arguments from left to right only until the answer is known,
+/// and return `#t` or `#f`.
+fn or(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
     if vals.is_empty(){
         return EvalResult::Err("Must perform on at least one value".into());
     }
-
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::Symbol(s) => if s == "True" {
-                    Ok("True".to_string())
-                } else {
-                    Ok("False".to_string())
-                },
-                Expr::List(vs) => if vs.len() == 0 {
-                    Ok("False".to_string())
-                } else {
-                    Ok("True".to_string())
-                },
-                _ => Err("Can only perform on boolean values.".into()),
-            },
-            _ => Err("Can only perform on boolean values.".into()),
-        })
-        .collect::<Result<Vec<String>, String>>();
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| if xs.iter().any(|x| x == "False") {
-            EvalResult::Expr(Expr::symbol("False"))
-        } else {
-            EvalResult::Expr(Expr::symbol("True"))
-        },
-    )
+    for e in vals {
+        match condition(e, env, "Cannot use Unit as a condition.") {
+            Ok(true) => return EvalResult::Expr(Expr::boolean(true)),
+            Ok(false) => {}
+            Err(err) => return err,
+        }
+    }
+    EvalResult::Expr(Expr::boolean(false))
 }
 
-fn not(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+fn and(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
     if vals.is_empty(){
         return EvalResult::Err("Must perform on at least one value".into());
     }
+    for e in vals {
+        match condition(e, env, "Cannot use Unit as a condition.") {
+            Ok(true) => {}
+            Ok(false) => return EvalResult::Expr(Expr::boolean(false)),
+            Err(err) => return err,
+        }
+    }
+    EvalResult::Expr(Expr::boolean(true))
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::Symbol(s) => if s == "True" {
-                    Ok("False".to_string())
-                } else {
-                    Ok("True".to_string())
-                },
-                Expr::List(vs) => if vs.len() == 0 {
-                    Ok("True".to_string())
-                } else {
-                    Ok("False".to_string())
-                },
-                _ => Err("Can only perform on boolean values.".into()),
-            },
-                _ =>Err("Can only perform on boolean values.".into()),
-        })
-        .collect::<Result<Vec<String>, String>>();
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| EvalResult::Expr(Expr::symbol(&xs[0])),
-    )
+fn not(vals: &[Rc<Expr>]) -> EvalResult {
+    EvalResult::Expr(Expr::boolean(!vals[0].is_truthy()))
 }
 
-fn eq(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty() || vals.len() == 1 {
-        return EvalResult::Err("Must perform on at least two values".into());
+/// Whether two numbers have the same value. Floats are converted exactly, so `(= 1/2 0.5)` is
+/// true but `(= 1/3 0.3333333333333333)` is not; NaN and the infinities only compare as floats.
+fn numbers_equal(x: &Number, y: &Number) -> bool {
+    let exact = |n: &Number| match n {
+        Number::Float(f) => Rational::from_f64(*f),
+        n => n.to_rational(),
+    };
+    match (exact(x), exact(y)) {
+        (Some(a), Some(b)) => a == b,
+        _ => matches!((x, y), (Number::Float(a), Number::Float(b)) if a == b),
     }
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::FNum(f) => Ok(f.to_string()),
-                Expr::Symbol(s) => Ok(s.to_string()),
-                Expr::List(vs) => Ok(vs.iter().map(|e1| match eval (e1.clone(), env) {
-                    EvalResult::Expr(exp1) => match &*exp1 {
-                        Expr::FNum(f) => f.to_string(),
-                        Expr::Symbol(s) => s.to_string(),
-                        _ => "Error".to_string(), 
+/// The rule `=` uses: numbers are equal when their values are, lists, dotted lists and vectors
+/// when their elements are pairwise equal, maps when they have equal values for the same keys,
+/// and functions only to themselves. Anything else must be the same type with the same value.
+fn values_equal(a: &Expr, b: &Expr) -> bool {
+    let mut pending = vec![(a, b)];
+    while let Some((a, b)) = pending.pop() {
+        let equal = match (a, b) {
+            (Expr::List(xs), Expr::List(ys)) | (Expr::Vector(xs), Expr::Vector(ys)) => {
+                pending.extend(xs.iter().zip(ys).map(|(x, y)| (&**x, &**y)));
+                xs.len() == ys.len()
+            },
+            (Expr::DottedList(xs, t1), Expr::DottedList(ys, t2)) => {
+                pending.extend(xs.iter().zip(ys).map(|(x, y)| (&**x, &**y)));
+                pending.push((&**t1, &**t2));
+                xs.len() == ys.len()
+            },
+            (Expr::Map(kvs1), Expr::Map(kvs2)) => kvs1.len() == kvs2.len() && kvs1.iter().all(|(k, v1)| {
+                match kvs2.iter().find(|(key, _)| key == k) {
+                    Some((_, v2)) => {
+                        pending.push((&**v1, &**v2));
+                        true
                     },
-                    _ => "Error".to_string(),
-                })
-                .collect::<String>()),
+                    None => false,
+                }
+            }),
+            (Expr::Lambda(_), Expr::Lambda(_)) | (Expr::Native(_), Expr::Native(_)) => std::ptr::eq(a, b),
+            _ => match (as_number(a), as_number(b)) {
+                (Some(x), Some(y)) => numbers_equal(&x, &y),
+                (None, None) => a == b,
+                _ => false,
             },
-                _ => Err("Can only perform on boolean values.".into()),
-        })
-        .collect::<Result<Vec<String>, String>>();
-
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| if xs.iter().all(|x| x == &xs[0]) {
-            EvalResult::Expr(Expr::symbol("True"))
-        } else {
-            EvalResult::Expr(Expr::symbol("False"))
+        };
+        if !equal {
+            return false;
         }
-    )
+    }
+    true
 }
 
-fn neq(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
-    if vals.is_empty() || vals.len() == 1 {
-        return EvalResult::Err("Must perform on at least two values".into());
-    }
+fn eq(vals: &[Rc<Expr>]) -> EvalResult {
+    EvalResult::Expr(Expr::boolean(vals.windows(2).all(|w| values_equal(&w[0], &w[1]))))
+}
 
-    let total = vals
-        .iter()
-        .map(|e| match eval(e.clone(), env){
-            EvalResult::Expr(exp) => match &*exp {
-                Expr::FNum(f) => Ok(f.to_string()),
-                Expr::Symbol(s) => Ok(s.to_string()),
-                Expr::List(vs) => Ok(vs.iter().map(|e1| match eval (e1.clone(), env) {
-                    EvalResult::Expr(exp1) => match &*exp1 {
-                        Expr::FNum(f) => f.to_string(),
-                        Expr::Symbol(s) => s.to_string(),
-                        _ => "Error".to_string(),
-                    },
-                    _ => "Error".to_string(),
-                })
-                .collect::<String>()),
-            },
-                _ => Err("Can only perform on boolean values.".into()),
+fn neq(vals: &[Rc<Expr>]) -> EvalResult {
+    EvalResult::Expr(Expr::boolean(!vals.windows(2).all(|w| values_equal(&w[0], &w[1]))))
+}
+
+/// The character builtins that take a single character.
+const CHAR_OPS: &[&str] = &[
+    "char->integer",
+    "char-upcase",
+    "char-downcase",
+    "char-alphabetic?",
+    "char-numeric?",
+    "char-whitespace?",
+    "char-upper-case?",
+    "char-lower-case?",
+];
+
+/// The character builtins that compare two or more characters.
+const CHAR_COMPARISONS: &[&str] = &["char=?", "char<?", "char>?"];
+
+fn char_args(op: &str, vals: &[Rc<Expr>]) -> Result<Vec<char>, String> {
+    vals.iter()
+        .map(|exp| match &**exp {
+            Expr::Char(c) => Ok(*c),
+            _ => Err(format!("{} can only be applied to characters.", op)),
         })
-        .collect::<Result<Vec<String>, String>>();
+        .collect()
+}
 
-    total.map_or_else(
-        |err| EvalResult::Err(err),
-        |xs| if xs.iter().all(|x| x == &xs[0]) {
-            EvalResult::Expr(Expr::symbol("False"))
-        } else {
-            EvalResult::Expr(Expr::symbol("True"))
-        }
+/// Maps a character to a single character, leaving it alone if the mapping would produce more
+/// than one (e.g. `ß` upper-cases to `SS`).
+fn map_char<I: Iterator<Item = char>>(c: char, mapped: I) -> char {
+    let mapped: Vec<char> = mapped.collect();
+    if mapped.len() == 1 { mapped[0] } else { c }
+}
+
+fn char_op(op: &str, vals: &[Rc<Expr>]) -> EvalResult {
+    let c = match char_args(op, vals) {
+        Ok(cs) => cs[0],
+        Err(err) => return EvalResult::Err(err),
+    };
+    let result = match op {
+        "char->integer" => Expr::int(c as i64),
+        "char-upcase" => Expr::character(map_char(c, c.to_uppercase())),
+        "char-downcase" => Expr::character(map_char(c, c.to_lowercase())),
+        "char-alphabetic?" => Expr::boolean(c.is_alphabetic()),
+        "char-numeric?" => Expr::boolean(c.is_numeric()),
+        "char-whitespace?" => Expr::boolean(c.is_whitespace()),
+        "char-upper-case?" => Expr::boolean(c.is_uppercase()),
+        "char-lower-case?" => Expr::boolean(c.is_lowercase()),
+        _ => return EvalResult::Err(format!("Unknown character operation {}", op)),
+    };
+    EvalResult::Expr(result)
+}
+
+fn char_comparison(op: &str, vals: &[Rc<Expr>]) -> EvalResult {
+    let cmp: fn(&char, &char) -> bool = match op {
+        "char=?" => char::eq,
+        "char<?" => char::lt,
+        "char>?" => char::gt,
+        _ => return EvalResult::Err(format!("Unknown character operation {}", op)),
+    };
+    char_args(op, vals).map_or_else(
+        EvalResult::Err,
+        |cs| EvalResult::Expr(Expr::boolean(cs.windows(2).all(|w| cmp(&w[0], &w[1])))),
     )
 }
 
+fn integer_to_char(vals: &[Rc<Expr>]) -> EvalResult {
+    match &*vals[0] {
+        Expr::Int(n) if (0..=u32::MAX as i64).contains(n) => {
+            std::char::from_u32(*n as u32).map_or_else(
+                || EvalResult::Err(format!("{} is not a valid character code.", n)),
+                |c| EvalResult::Expr(Expr::character(c)),
+            )
+        },
+        _ => EvalResult::Err("integer->char can only be applied to a non-negative integer.".into()),
+    }
+}
+
 fn if_then_else(blocks: &[Rc<Expr>], env: &mut Environment) -> EvalResult{
     if blocks.len() != 3 {
         return EvalResult::Err("If Expressions must have the format (if (<prediacte block>) (<then block>) (<else block>))".into());
     }
-    match eval(blocks[0].clone(), env){
-        EvalResult::Expr(expr) => {
-            match &*expr {
-                Expr::List(vs) if vs.len() == 0 => eval(blocks[2].clone(),env),
-                _ => eval(blocks[1].clone(), env),
-            }
-        },
-        EvalResult::Unit => EvalResult::Err("If expression predicates must return an expression.".into()),
-        err => err
+    match condition(&blocks[0], env, "If expression predicates must return an expression.") {
+        Ok(true) => eval(blocks[1].clone(), env),
+        Ok(false) => eval(blocks[2].clone(), env),
+        Err(err) => err,
     }
 }
 
+/// Builds a function value from `(lambda (arg1 arg2 .. argn) <Expr>)`, where `vals` is
+/// everything after `lambda`. `(a b . rest)` collects any further arguments into `rest`, and a
+/// bare symbol such as `args` collects all of them.
+fn make_lambda(vals: &[Rc<Expr>], env: &Environment) -> Result<Rc<Expr>, String> {
+    if vals.len() != 2 {
+        return Err("Lambda expressions must follow the pattern (lambda (arg1 arg2 arg3 .. argn) <Expr>)".into());
+    }
+    let (params, rest) = match &*vals[0] {
+        Expr::DottedList(params, rest) => (params.as_slice(), Some(rest)),
+        Expr::List(params) => (params.as_slice(), None),
+        Expr::Symbol(_) => (&[][..], Some(&vals[0])),
+        _ => return Err("Lambda expressions must follow the pattern (lambda (arg1 arg2 arg3 .. argn) <Expr>)".into()),
+    };
+    let param_name = |e: &Rc<Expr>| match &**e {
+        Expr::Symbol(n) => Ok(n.to_string()),
+        _ => Err("Function parameters must be symbols.".to_string()),
+    };
+    let params = params.iter().map(param_name).collect::<Result<Vec<String>, String>>()?;
+    let rest = rest.map(param_name).transpose()?;
+    Ok(Expr::lambda(&params, rest.as_deref(), vals[1].clone(), &env.contexts))
+}
+
+fn lambda(vals: &[Rc<Expr>], env: &Environment) -> EvalResult {
+    make_lambda(vals, env).map_or_else(EvalResult::Err, EvalResult::Expr)
+}
+
+/// `(fn fn-name (arg1 .. argn) <Expr>)` is shorthand for `(let fn-name (lambda (arg1 .. argn) <Expr>))`.
 fn add_fn_to_env(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
     if vals.len() != 3{
         return EvalResult::Err("Function definitions must follow the pattern of (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>".into());
     }
-    let fn_name = &*vals[0];
-    let p_names = &*vals[1];
-    let body = &vals[2];
-    match (&*fn_name, p_names, body){
-        (Expr::Symbol(fn_name), Expr::List(params), body) => {
-            let ps: Result<Vec<String>, String> = params.iter().cloned().map(|e| {
-                if let Expr::Symbol(n) = &*e {
-                    Ok(n.to_string())
-                }else{
-                    Err("Function parameters must be symbols.".into())
-                }
-            })
-            .collect();
+    match &*vals[0] {
+        Expr::Symbol(fn_name) => make_lambda(&vals[1..], env)
+            .and_then(|f| env.add_var(fn_name, f))
+            .map_or_else(EvalResult::Err, |_| EvalResult::Unit),
+        _ => EvalResult::Err("Function definitions must follow the pattern (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>".into()),
+    }
+}
 
-            ps.map_or_else(
-                |err| EvalResult::Err(err),
-                |xs| env.add_fn(fn_name, xs.as_slice(), body.clone()).map_or_else(
-                    |err| EvalResult::Err(err),
-                    |_| EvalResult::Unit
-                )
-            )
+/// Checks whether `xs` is a two element form headed by the symbol `name`, e.g. `(quote x)`.
+fn is_form(xs: &[Rc<Expr>], name: &str) -> bool {
+    xs.len() == 2 && matches!(&*xs[0], Expr::Symbol(s) if s == name)
+}
+
+fn quote(vals: &[Rc<Expr>]) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err("Quote expressions must have the format (quote <datum>)".into());
+    }
+    EvalResult::Expr(vals[0].clone())
+}
+
+fn eval_unquoted(expr: Rc<Expr>, env: &mut Environment) -> Result<Rc<Expr>, String> {
+    match eval(expr, env) {
+        EvalResult::Expr(e) => Ok(e),
+        EvalResult::Err(err) => Err(err),
+        EvalResult::Unit => Err("Cannot unquote an expression that evaluates to Unit.".into()),
+    }
+}
+
+/// Expands a quasiquote template, evaluating the `unquote`d parts that belong to the outermost
+/// quasiquote. `depth` counts how many quasiquotes enclose `template`.
+fn expand_quasiquote(template: Rc<Expr>, depth: usize, env: &mut Environment) -> Result<Rc<Expr>, String> {
+    match &*template {
+        Expr::List(xs) if is_form(xs, "unquote") => if depth == 1 {
+            eval_unquoted(xs[1].clone(), env)
+        } else {
+            Ok(Expr::list(&[xs[0].clone(), expand_quasiquote(xs[1].clone(), depth - 1, env)?]))
         },
-        _ => EvalResult::Err("Function definitions must follow the pattern (fn fn-name (arg1 arg2 arg3 .. argn) <Expr>".into()),
+        Expr::List(xs) if is_form(xs, "unquote-splicing") => if depth == 1 {
+            Err("unquote-splicing must appear inside a list.".into())
+        } else {
+            Ok(Expr::list(&[xs[0].clone(), expand_quasiquote(xs[1].clone(), depth - 1, env)?]))
+        },
+        Expr::List(xs) if is_form(xs, "quasiquote") => {
+            Ok(Expr::list(&[xs[0].clone(), expand_quasiquote(xs[1].clone(), depth + 1, env)?]))
+        },
+        // `(a . ,b)` reads as `(a unquote b)`, so an unquote in the second to last place is the tail.
+        Expr::List(xs) if depth == 1 && xs.len() > 2 && matches!(&*xs[xs.len() - 2], Expr::Symbol(s) if s == "unquote") => {
+            let head = expand_quasiquote_elements(&xs[..xs.len() - 2], depth, env)?;
+            Ok(Expr::dotted(&head, eval_unquoted(xs[xs.len() - 1].clone(), env)?))
+        },
+        Expr::List(xs) => Ok(Expr::list(&expand_quasiquote_elements(xs, depth, env)?)),
+        Expr::DottedList(xs, tail) => {
+            let head = expand_quasiquote_elements(xs, depth, env)?;
+            Ok(Expr::dotted(&head, expand_quasiquote(tail.clone(), depth, env)?))
+        },
+        Expr::Vector(xs) => Ok(Expr::vector(&expand_quasiquote_elements(xs, depth, env)?)),
+        _ => Ok(template),
+    }
+}
+
+/// Expands the elements of a list or vector template, splicing in any `unquote-splicing`s.
+fn expand_quasiquote_elements(xs: &[Rc<Expr>], depth: usize, env: &mut Environment) -> Result<Vec<Rc<Expr>>, String> {
+    let mut out = Vec::new();
+    for x in xs {
+        match &**x {
+            Expr::List(inner) if depth == 1 && is_form(inner, "unquote-splicing") => {
+                match &*eval_unquoted(inner[1].clone(), env)? {
+                    Expr::List(spliced) | Expr::Vector(spliced) => out.extend(spliced.iter().cloned()),
+                    _ => return Err("unquote-splicing expects its argument to evaluate to a list.".into()),
+                }
+            },
+            _ => out.push(expand_quasiquote(x.clone(), depth, env)?),
+        }
+    }
+    Ok(out)
+}
+
+fn quasiquote(vals: &[Rc<Expr>], env: &mut Environment) -> EvalResult {
+    if vals.len() != 1 {
+        return EvalResult::Err("Quasiquote expressions must have the format (quasiquote <template>)".into());
     }
-    
+    expand_quasiquote(vals[0].clone(), 1, env).map_or_else(EvalResult::Err, EvalResult::Expr)
+}
+
+/// Writes the arguments to standard out.
+fn print(vals: &[Rc<Expr>]) -> EvalResult {
+    println!("{}", gen_print_output(vals));
+    EvalResult::Unit
+}
+
+/// Evaluates each element of a vector or map literal; none of them may evaluate to Unit.
+fn eval_elements(exprs: &[Rc<Expr>], env: &mut Environment) -> Result<Vec<Rc<Expr>>, String> {
+    exprs
+        .iter()
+        .map(|expr| match eval(expr.clone(), env) {
+            EvalResult::Expr(e) => Ok(e),
+            EvalResult::Err(err) => Err(err),
+            EvalResult::Unit => Err("Vector and map elements cannot be Unit.".into()),
+        })
+        .collect()
+}
+
+thread_local! {
+    /// The builtin functions. Every environment sees them beneath its own contexts, so a program
+    /// can shadow them but not remove them.
+    static BUILTINS: Context = builtins();
+}
+
+fn builtins() -> Context {
+    let mut env = Environment::empty();
+    env.register_native("+", Arity::AtLeast(1), add_vals);
+    env.register_native("-", Arity::AtLeast(1), sub_vals);
+    env.register_native("*", Arity::AtLeast(1), mul_vals);
+    env.register_native("/", Arity::AtLeast(1), div_vals);
+    for &op in INTEGER_DIVISION_OPS {
+        env.register_native(op, Arity::Exactly(2), move |vals| integer_division(op, vals));
+    }
+    for &op in NUMBER_OPS {
+        env.register_native(op, Arity::Exactly(1), move |vals| number_op(op, vals));
+    }
+    env.register_native("not", Arity::Exactly(1), not);
+    env.register_native("=", Arity::AtLeast(2), eq);
+    env.register_native("!=", Arity::AtLeast(2), neq);
+    for &op in CHAR_OPS {
+        env.register_native(op, Arity::Exactly(1), move |vals| char_op(op, vals));
+    }
+    for &op in CHAR_COMPARISONS {
+        env.register_native(op, Arity::AtLeast(2), move |vals| char_comparison(op, vals));
+    }
+    env.register_native("integer->char", Arity::Exactly(1), integer_to_char);
+    env.register_native("print", Arity::AtLeast(0), print);
+    env.contexts.remove(0)
 }
 
 /// Evaluates the given expression.
 pub fn eval(e: Rc<Expr>, env: &mut Environment) -> EvalResult {
     match &*e {
-        Expr::FNum(_) => EvalResult::Expr(e.clone()),
-        Expr::Symbol(s) => evaluate_symbol(e.clone(), s, &[], env),
+        Expr::Int(_) | Expr::BigInt(_) | Expr::Rational(_) | Expr::FNum(_) | Expr::Str(_) | Expr::Char(_) | Expr::Bool(_) => EvalResult::Expr(e.clone()),
+        Expr::Vector(xs) => eval_elements(xs, env)
+            .map_or_else(EvalResult::Err, |xs| EvalResult::Expr(Expr::vector(&xs))),
+        Expr::Map(kvs) => {
+            let flat: Vec<Rc<Expr>> = kvs.iter().flat_map(|(k, v)| vec![k.clone(), v.clone()]).collect();
+            eval_elements(&flat, env).map_or_else(EvalResult::Err, |xs| {
+                let pairs: Vec<(Rc<Expr>, Rc<Expr>)> = xs.chunks(2).map(|kv| (kv[0].clone(), kv[1].clone())).collect();
+                EvalResult::Expr(Expr::map(&pairs))
+            })
+        },
+        Expr::DottedList(..) => EvalResult::Err(format!("Cannot evaluate the improper list {}", e)),
+        Expr::Lambda(_) | Expr::Native(_) => EvalResult::Expr(e.clone()),
+        Expr::Symbol(s) => EvalResult::Expr(env.lookup(s).unwrap_or_else(|| e.clone())),
         Expr::List(vals) => {
             if vals.is_empty(){
                 return EvalResult::Expr(Expr::list(&[]));
             }
             let op = &*vals[0];
             match op{
-                Expr::Symbol(s) if s == "+" => add_vals(&vals[1..], env),
+                Expr::Symbol(s) if s == "or" => or(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "-" => sub_vals(&vals[1..], env),
+                Expr::Symbol(s) if s == "and" => and(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "*" => mul_vals(&vals[1..], env),
+                Expr::Symbol(s) if s == "let" => add_var_to_env(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "/" => div_vals(&vals[1..], env),
+                Expr::Symbol(s) if s == "fn" => add_fn_to_env(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "or" => or(&vals[1..], env),
+                Expr::Symbol(s) if s == "lambda" => lambda(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "and" => and(&vals[1..], env),
+                Expr::Symbol(s) if s == "set!" => set_var(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "not" => not(&vals[1..], env),
+                Expr::Symbol(s) if s == "begin" => begin(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "=" => eq(&vals[1..], env),
-                
-                Expr::Symbol(s) if s == "!=" => neq(&vals[1..], env),
+                Expr::Symbol(s) if s == "if" => if_then_else(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "let" => add_var_to_env(&vals[1..], env),
+                Expr::Symbol(s) if s == "quote" => quote(&vals[1..]),
 
-                Expr::Symbol(s) if s == "fn" => add_fn_to_env(&vals[1..], env),
+                Expr::Symbol(s) if s == "quasiquote" => quasiquote(&vals[1..], env),
 
-                Expr::Symbol(s) if s == "print" => {
-                    let output: Vec<String> = vals[1..].iter()
-                        .cloned()
-                        .map(|expr| gen_print_output(expr, env))
-                        .collect();
-                    println!("{}",output.join(" "));
-                    EvalResult::Unit
+                Expr::Symbol(s) if s == "unquote" || s == "unquote-splicing" => {
+                    EvalResult::Err(format!("{} is only valid inside a quasiquote.", s))
                 },
-                Expr::Symbol(s) if s == "if" => if_then_else(&vals[1..], env),
-                Expr::Symbol(s) if env.contains_key(&s) => {
-                    evaluate_symbol(e.clone(), s, &vals[1..],env)
-                }
 
+                // Anything else is a call if its head evaluates to a function, and otherwise a list
+                // of the values of its elements.
                 _ => {
-                    let res: Result<Vec<Rc<Expr>>, EvalResult> = vals.iter()
-                        .cloned()
-                        .map(|expr| eval(expr,env))
+                    let head = eval(vals[0].clone(), env);
+                    if let EvalResult::Expr(value) = &head {
+                        match &**value {
+                            Expr::Lambda(_) | Expr::Native(_) => {
+                                return eval_args(&vals[1..], env)
+                                    .map_or_else(EvalResult::Err, |args| apply(value, &args, env));
+                            }
+                            // A variable in head position, as in `(x)`, evaluates to its value.
+                            _ if matches!(&*vals[0], Expr::Symbol(s) if env.contains_key(s)) => {
+                                return EvalResult::Expr(value.clone());
+                            }
+                            _ => {}
+                        }
+                    }
+                    let res: Result<Vec<Rc<Expr>>, EvalResult> = std::iter::once(head)
+                        .chain(vals[1..].iter().map(|expr| eval(expr.clone(), env)))
                         .filter(|x| *x != EvalResult::Unit)
                         .map(|x| if let EvalResult::Expr(expr) = x {
                             Ok(expr)
@@ -553,8 +899,3 @@ pub fn eval(e: Rc<Expr>, env: &mut Environment) -> EvalResult {
         }
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-}
@@ -0,0 +1,228 @@
+use crate::cst::{parse_cst, CstError, Node, SyntaxToken, Trivia, TriviaKind};
+use crate::lex::TokenKind;
+use crate::number::parse_number;
+
+/// Options for `format_source`.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    /// The column a line should not extend past, where possible.
+    pub line_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions { line_width: 80 }
+    }
+}
+
+/// Reformats mlisp source into the canonical style:
+/// - a form that fits in the remaining width is written on one line
+/// - otherwise `fn` keeps its name and parameters, and `let` and `if` their first argument, on the
+///   opening line; the bodies of `fn` and `let` are indented by two columns and the branches of
+///   `if` line up with its predicate
+/// - other calls line up their arguments with the first one, and other sequences, including quoted
+///   lists and lists that start with a number, line up their elements one column in from the
+///   opening delimiter
+/// - comments are kept, as are single blank lines between forms
+///
+/// Formatting is idempotent: formatting formatted source gives it back unchanged.
+pub fn format_source(source: &str, options: FormatOptions) -> Result<String, CstError> {
+    let tree = parse_cst(source)?;
+    let mut formatter = Formatter { out: String::new(), options };
+    for node in &tree.nodes {
+        formatter.separate(&node.first_token().leading, 0);
+        formatter.node(node, 0, false);
+    }
+    formatter.comments(&tree.trailing, 0);
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n');
+    }
+    Ok(formatter.out)
+}
+
+struct Formatter {
+    out: String,
+    options: FormatOptions,
+}
+
+fn is_comment(trivia: &Trivia) -> bool {
+    trivia.kind != TriviaKind::Whitespace
+}
+
+fn has_comments(token: &SyntaxToken) -> bool {
+    token.leading.iter().any(is_comment)
+}
+
+fn is_prefix(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::DatumComment
+            | TokenKind::Quote
+            | TokenKind::Quasiquote
+            | TokenKind::Unquote
+            | TokenKind::UnquoteSplicing
+    )
+}
+
+/// The node written on a single line, or `None` if it can't be because it holds comments or
+/// multi-line strings.
+fn flat(node: &Node) -> Option<String> {
+    let tokens = node.tokens();
+    let mut text = String::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if (i > 0 && has_comments(token)) || token.text.contains('\n') {
+            return None;
+        }
+        let after_open = i > 0 && {
+            let prev = &tokens[i - 1].token.kind;
+            is_prefix(prev) || matches!(prev, TokenKind::LPar | TokenKind::LBracket | TokenKind::LBrace)
+        };
+        let before_close = matches!(token.token.kind, TokenKind::RPar | TokenKind::RBracket | TokenKind::RBrace);
+        if i > 0 && !after_open && !before_close {
+            text.push(' ');
+        }
+        text += token.text;
+    }
+    Some(text)
+}
+
+impl Formatter {
+    fn column(&self) -> usize {
+        self.out.rsplit('\n').next().map_or(0, |line| line.chars().count())
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+    }
+
+    /// Writes the comments in `trivia`, each on its own line at `indent`, except that a comment
+    /// that followed other code on its line stays there. Returns how many line breaks followed the
+    /// last comment (or began the trivia, if it has no comments).
+    fn comments(&mut self, trivia: &[Trivia], indent: usize) -> usize {
+        let mut breaks = 0;
+        for t in trivia {
+            if !is_comment(t) {
+                breaks += t.text.matches('\n').count();
+                continue;
+            }
+            if breaks == 0 && !self.out.is_empty() && !self.out.ends_with(['\n', ' ']) {
+                self.out.push(' ');
+            } else if !self.out.is_empty() {
+                if breaks > 1 {
+                    self.out.push('\n');
+                }
+                self.newline(indent);
+            }
+            self.out += t.text;
+            breaks = 0;
+        }
+        breaks
+    }
+
+    /// Writes the comments in `trivia` and starts a new line at `indent` for what comes after,
+    /// keeping a blank line if there was one.
+    fn separate(&mut self, trivia: &[Trivia], indent: usize) {
+        let breaks = self.comments(trivia, indent);
+        if self.out.is_empty() {
+            return;
+        }
+        if breaks > 1 {
+            self.out.push('\n');
+        }
+        self.newline(indent);
+    }
+
+    /// Whether `text` fits on the current line, followed by `trailing` more columns.
+    fn fits(&self, text: &str, trailing: usize) -> bool {
+        self.column() + text.chars().count() + trailing <= self.options.line_width
+    }
+
+    /// Writes `node` starting at the current column, on one line if it fits. `trailing` is the
+    /// width of the closing delimiters that will directly follow it, and `quoted` is whether the
+    /// node is quoted data rather than code.
+    fn node(&mut self, node: &Node, trailing: usize, quoted: bool) {
+        if let Some(text) = flat(node) {
+            if self.fits(&text, trailing) {
+                self.out += &text;
+                return;
+            }
+        }
+        match node {
+            Node::Atom(token) => self.out += token.text,
+            Node::Prefixed { prefix, comments, node } => {
+                self.out += prefix.text;
+                let quoted = match prefix.token.kind {
+                    TokenKind::Quote | TokenKind::Quasiquote => true,
+                    TokenKind::Unquote | TokenKind::UnquoteSplicing => false,
+                    _ => quoted,
+                };
+                let indent = self.column();
+                for (i, next) in comments.iter().chain(Some(&**node)).enumerate() {
+                    if has_comments(next.first_token()) {
+                        self.separate(&next.first_token().leading, indent);
+                    } else if i > 0 {
+                        self.out.push(' ');
+                    }
+                    self.node(next, if i == comments.len() { trailing } else { 0 }, quoted);
+                }
+            }
+            Node::Seq { open, children, close } => self.seq(open, children, close, trailing, quoted),
+        }
+    }
+
+    fn seq(&mut self, open: &SyntaxToken, children: &[Node], close: &SyntaxToken, trailing: usize, quoted: bool) {
+        let open_col = self.column();
+        self.out += open.text;
+
+        // How many children share the opening line and where the rest are indented to, or `None`
+        // to fill lines with as many atoms as fit. Quoted lists, and lists that start with a
+        // number, are data rather than calls.
+        let head = match (&open.token.kind, children.first()) {
+            (TokenKind::LPar, Some(Node::Atom(t)))
+                if !quoted && matches!(&t.token.kind, TokenKind::Literal(s) if parse_number(s).is_none()) =>
+            {
+                match t.text {
+                    "fn" => Some((3, open_col + 2)),
+                    "let" => Some((2, open_col + 2)),
+                    "if" => Some((2, open_col + 4)),
+                    _ => Some((2, open_col + t.text.chars().count() + 2)),
+                }
+            }
+            (TokenKind::LBrace, _) => Some((1, open_col + 1)),
+            _ => None,
+        };
+        let indent = head.map_or(open_col + 1, |(_, indent)| indent);
+        let close_trailing = if has_comments(close) { 0 } else { trailing + close.text.chars().count() };
+
+        let mut on_opening_line = true;
+        for (i, child) in children.iter().enumerate() {
+            let first = child.first_token();
+            let child_trailing = if i + 1 == children.len() { close_trailing } else { 0 };
+            let same_line = i == 0
+                || match head {
+                    // Map values stay with their keys.
+                    _ if open.token.kind == TokenKind::LBrace => i % 2 == 1,
+                    Some((count, _)) => on_opening_line && i < count,
+                    None => matches!(child, Node::Atom(_)) && self.fits(&format!(" {}", first.text), child_trailing),
+                };
+            if same_line && !has_comments(first) {
+                if i > 0 {
+                    self.out.push(' ');
+                }
+            } else {
+                on_opening_line = false;
+                self.separate(&first.leading, indent);
+            }
+            self.node(child, child_trailing, quoted);
+        }
+
+        if has_comments(close) {
+            self.comments(&close.leading, indent);
+            if close.leading.iter().rev().find(|t| is_comment(t)).is_some_and(|t| t.kind == TriviaKind::LineComment) {
+                self.newline(indent);
+            }
+        }
+        self.out += close.text;
+    }
+}
@@ -1,20 +1,91 @@
 use crate::lex::lex;
-use crate::parse::parse;
-use crate::eval::{eval, Environment, EvalResult};
+use crate::parse::parse_program_recovering;
+use crate::eval::{apply, eval, Environment, EvalResult};
+use crate::types::{Arity, Expr};
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
 
-/// Lexes, parses, and evaluates the given program.
-pub fn run_interpreter(program: &str) -> EvalResult {
-	match lex(&program) {
-		Err(err) => EvalResult::Err("Lex error".into()),
-		Ok(tokens) => match parse(&tokens) {
-			Err(err) => EvalResult::Err("Parse error".into()),
-			Ok(expr) => {
-				let mut env = Environment::default();
-				match eval(expr.clone(), &mut env){
-					EvalResult::Expr(n) => EvalResult::Expr(n),
-					_ => EvalResult::Err("Eval error".into())
+/// An interpreter that keeps its definitions between calls, so a host application can load a
+/// program once and then call into it.
+pub struct Interpreter {
+	env: Environment,
+}
+
+impl Default for Interpreter {
+	fn default() -> Interpreter {
+		Interpreter::new()
+	}
+}
+
+impl Interpreter {
+	pub fn new() -> Interpreter {
+		Interpreter {
+			env: Environment::default(),
+		}
+	}
+
+	/// Lexes, parses, and evaluates the given program one top-level form at a time, stopping at
+	/// the first error. Returns the result of the last form, or `Unit` for an empty program.
+	///
+	/// Nothing is evaluated if the program has parse errors; they are all reported, one per line.
+	pub fn eval_str(&mut self, program: &str) -> EvalResult {
+		match lex(program) {
+			Err(err) => EvalResult::Err(format!("Lex error: {}", err)),
+			Ok(tokens) => match parse_program_recovering(&tokens) {
+				(_, errors) if !errors.is_empty() => EvalResult::Err(
+					errors.iter().map(|err| format!("Parse error: {}", err)).collect::<Vec<_>>().join("\n")
+				),
+				(forms, _) => {
+					let mut result = EvalResult::Unit;
+					for form in forms {
+						result = eval(form, &mut self.env);
+						if let EvalResult::Err(_) = result {
+							break;
+						}
+					}
+					result
 				}
 			}
 		}
 	}
+
+	/// Reads the file at `path` and evaluates it as with `eval_str`.
+	pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> EvalResult {
+		let path = path.as_ref();
+		match fs::read_to_string(path) {
+			Ok(program) => self.eval_str(&program),
+			Err(err) => EvalResult::Err(format!("Cannot read {}: {}", path.display(), err)),
+		}
+	}
+
+	/// Calls the function bound to `name` with `args`, which are passed as they are rather than
+	/// evaluated.
+	pub fn call(&mut self, name: &str, args: &[Rc<Expr>]) -> EvalResult {
+		match self.env.lookup(name) {
+			Some(f) if matches!(&*f, Expr::Lambda(_) | Expr::Native(_)) => apply(&f, args, &mut self.env),
+			Some(_) => EvalResult::Err(format!("{} is not a function.", name)),
+			None => EvalResult::Err(format!("{} is not defined.", name)),
+		}
+	}
+
+	/// Returns the value bound to `name` at the top level, which includes the builtins.
+	pub fn get_global(&self, name: &str) -> Option<Rc<Expr>> {
+		self.env.lookup(name)
+	}
+
+	/// Binds `name` at the top level, replacing any existing definition.
+	pub fn set_global(&mut self, name: &str, value: Rc<Expr>) {
+		self.env.add_global(name, value);
+	}
+
+	/// Makes a function implemented in Rust callable by name; see `Environment::register_native`.
+	pub fn register_native(&mut self, name: &str, arity: Arity, func: impl Fn(&[Rc<Expr>]) -> EvalResult + 'static) {
+		self.env.register_native(name, arity, func);
+	}
+}
+
+/// Evaluates the given program in a fresh `Interpreter`; see `Interpreter::eval_str`.
+pub fn run_interpreter(program: &str) -> EvalResult {
+	Interpreter::new().eval_str(program)
 }
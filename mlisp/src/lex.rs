@@ -1,57 +1,592 @@
-#[derive(Debug)]
-pub enum Token {
+use crate::number::parse_number;
+use crate::span::{Pos, Span};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::str::Chars;
+
+/// The kind of a token. Text is borrowed from the input where possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind<'a> {
     LPar,
     RPar,
-    Literal(String),
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Literal(Cow<'a, str>),
+    Str(Cow<'a, str>),
+    /// A character literal such as `#\a`, `#\space` or `#\x41`.
+    Char(char),
+    /// A boolean literal: `#t` or `#true`, `#f` or `#false`.
+    Bool(bool),
+    /// `#;`, which comments out the datum that follows it.
+    DatumComment,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
 }
 
-impl PartialEq for Token {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Token::Literal(l1), Token::Literal(l2)) => l1 == l2,
-            (Token::LPar, Token::LPar)
-            | (Token::RPar, Token::RPar) => true,
-            _ => false,
-        }
+/// A token together with the region of source it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Span,
+}
+
+impl<'a> Token<'a> {
+    pub fn new(kind: TokenKind<'a>, span: Span) -> Token<'a> {
+        Token { kind, span }
+    }
+
+    /// Copies any borrowed text so the token no longer refers to the input.
+    pub fn into_owned(self) -> Token<'static> {
+        let kind = match self.kind {
+            TokenKind::LPar => TokenKind::LPar,
+            TokenKind::RPar => TokenKind::RPar,
+            TokenKind::LBracket => TokenKind::LBracket,
+            TokenKind::RBracket => TokenKind::RBracket,
+            TokenKind::LBrace => TokenKind::LBrace,
+            TokenKind::RBrace => TokenKind::RBrace,
+            TokenKind::Literal(s) => TokenKind::Literal(Cow::Owned(s.into_owned())),
+            TokenKind::Str(s) => TokenKind::Str(Cow::Owned(s.into_owned())),
+            TokenKind::Char(c) => TokenKind::Char(c),
+            TokenKind::Bool(b) => TokenKind::Bool(b),
+            TokenKind::DatumComment => TokenKind::DatumComment,
+            TokenKind::Quote => TokenKind::Quote,
+            TokenKind::Quasiquote => TokenKind::Quasiquote,
+            TokenKind::Unquote => TokenKind::Unquote,
+            TokenKind::UnquoteSplicing => TokenKind::UnquoteSplicing,
+        };
+        Token::new(kind, self.span)
     }
 }
 
 #[derive(Debug)]
 pub enum LexError {
-    UnknownToken(String),
-}
-
-fn add_whitespace(input: &str) ->String{
-    input
-        .chars()
-        .fold(Vec::new(), |mut acc, c| {
-            if c == '(' || c == ')' {
-                acc.append(&mut vec![' ', c, ' ']);
-            } else {
-                acc.push(c);
-            }
-            acc
-    })
-        .iter()
-        .collect()
-}
-
-pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
-    let r = add_whitespace(input)
-        .split_ascii_whitespace()
-        .map( |p| {
-            match p {
-                "(" => Token::LPar,
-                ")" => Token::RPar,
-                _ => Token::Literal(p.to_string()),
-            }
-        })
-        .collect();
-    
-    Ok(r)
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
+    UnknownToken(String, Span),
+    UnterminatedString(Span),
+    InvalidEscape(String, Span),
+    UnterminatedComment(Span),
+    InvalidNumber(String, Span),
+    InvalidCharacter(char, Span),
+    /// Reading from the underlying `io::Read` failed, or it produced invalid UTF-8.
+    Io(io::Error, Span),
+}
+
+impl LexError {
+    /// The region of source the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnknownToken(_, span)
+            | LexError::UnterminatedString(span)
+            | LexError::InvalidEscape(_, span)
+            | LexError::UnterminatedComment(span)
+            | LexError::InvalidNumber(_, span)
+            | LexError::InvalidCharacter(_, span)
+            | LexError::Io(_, span) => *span,
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexError::UnknownToken(s, span) => write!(f, "{}: unknown token {:?}", span, s),
+            LexError::UnterminatedString(span) => write!(f, "{}: unterminated string literal", span),
+            LexError::InvalidEscape(s, span) => write!(f, "{}: invalid escape sequence {}", span, s),
+            LexError::UnterminatedComment(span) => write!(f, "{}: unterminated block comment", span),
+            LexError::InvalidNumber(s, span) => write!(f, "{}: invalid number literal {:?}", span, s),
+            LexError::InvalidCharacter(c, span) => write!(f, "{}: invalid character {:?}", span, c),
+            LexError::Io(err, span) => write!(f, "{}: {}", span, err),
+        }
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_ascii_whitespace() || "()[]{}\";".contains(c)
+}
+
+/// Characters that are not allowed outside of strings and comments: control characters other than
+/// ASCII whitespace, and non-ASCII whitespace.
+fn is_invalid_char(c: char) -> bool {
+    (c.is_control() || c.is_whitespace()) && !c.is_ascii_whitespace()
+}
+
+/// Resolves the name in a character literal such as `#\space` or `#\x41`.
+fn char_from_name(name: &str) -> Option<char> {
+    match name {
+        "space" => Some(' '),
+        "newline" | "linefeed" => Some('\n'),
+        "tab" => Some('\t'),
+        "return" => Some('\r'),
+        "nul" | "null" => Some('\0'),
+        "alarm" => Some('\u{7}'),
+        "backspace" => Some('\u{8}'),
+        "escape" => Some('\u{1b}'),
+        "delete" => Some('\u{7f}'),
+        _ => name
+            .strip_prefix('x')
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(std::char::from_u32),
+    }
+}
+
+/// A stream of characters the lexer reads from.
+pub trait CharSource<'a> {
+    /// The next character, without consuming it.
+    fn peek(&mut self) -> Option<char>;
+
+    /// The character after the next one, without consuming anything.
+    fn peek_second(&mut self) -> Option<char>;
+
+    /// Consumes and returns the next character.
+    fn bump(&mut self) -> Option<char>;
+
+    /// Marks the start of a token at byte `offset`; `text` is only asked for ranges after the
+    /// most recent mark.
+    fn mark(&mut self, offset: usize);
+
+    /// The source text between byte offsets `start` and `end`.
+    fn text(&self, start: usize, end: usize) -> Cow<'a, str>;
+
+    /// Takes any error hit while reading ahead. Once an error occurs the source reports end of
+    /// input.
+    fn take_error(&mut self) -> Option<io::Error>;
+}
+
+/// Reads characters from a string slice, lending out slices of it without copying.
+pub struct StrSource<'a> {
+    input: &'a str,
+    chars: Chars<'a>,
+}
+
+impl<'a> StrSource<'a> {
+    pub fn new(input: &'a str) -> StrSource<'a> {
+        StrSource {
+            input,
+            chars: input.chars(),
+        }
+    }
+}
+
+impl<'a> CharSource<'a> for StrSource<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.clone().next()
+    }
+
+    fn peek_second(&mut self) -> Option<char> {
+        self.chars.clone().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        self.chars.next()
+    }
+
+    fn mark(&mut self, _offset: usize) {}
+
+    fn text(&self, start: usize, end: usize) -> Cow<'a, str> {
+        Cow::Borrowed(&self.input[start..end])
+    }
+
+    fn take_error(&mut self) -> Option<io::Error> {
+        None
+    }
+}
+
+/// Reads characters from an `io::Read` a chunk at a time, decoding UTF-8 as it goes. Only the
+/// text of the token currently being read is kept around.
+pub struct ReadSource<R> {
+    reader: R,
+    /// Bytes read but not yet decoded, e.g. the first half of a multi-byte character.
+    pending: Vec<u8>,
+    lookahead: VecDeque<char>,
+    /// Characters consumed since the last `mark`, and the offset that mark was at.
+    recorded: String,
+    mark: usize,
+    eof: bool,
+    error: Option<io::Error>,
+}
+
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+impl<R: Read> ReadSource<R> {
+    pub fn new(reader: R) -> ReadSource<R> {
+        ReadSource {
+            reader,
+            pending: Vec::new(),
+            lookahead: VecDeque::new(),
+            recorded: String::new(),
+            mark: 0,
+            eof: false,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, err: io::Error) {
+        self.error = Some(err);
+        self.eof = true;
+    }
+
+    /// Decodes as much of `pending` as forms complete characters.
+    fn decode(&mut self) {
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(s) => s.len(),
+            Err(e) => {
+                if e.error_len().is_some() {
+                    self.fail(io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8"));
+                }
+                e.valid_up_to()
+            }
+        };
+        let decoded = std::str::from_utf8(&self.pending[..valid]).expect("prefix was validated above");
+        self.lookahead.extend(decoded.chars());
+        self.pending.drain(..valid);
+    }
+
+    /// Reads until at least `n` characters are buffered or the input is exhausted.
+    fn fill(&mut self, n: usize) {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        while self.lookahead.len() < n && !self.eof {
+            match self.reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.eof = true;
+                    if !self.pending.is_empty() {
+                        self.fail(io::Error::new(io::ErrorKind::UnexpectedEof, "stream ended inside a UTF-8 character"));
+                    }
+                }
+                Ok(read) => {
+                    self.pending.extend_from_slice(&chunk[..read]);
+                    self.decode();
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => self.fail(e),
+            }
+        }
+    }
+}
+
+impl<R: Read> CharSource<'static> for ReadSource<R> {
+    fn peek(&mut self) -> Option<char> {
+        self.fill(1);
+        self.lookahead.front().copied()
+    }
+
+    fn peek_second(&mut self) -> Option<char> {
+        self.fill(2);
+        self.lookahead.get(1).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        self.fill(1);
+        let c = self.lookahead.pop_front()?;
+        self.recorded.push(c);
+        Some(c)
+    }
+
+    fn mark(&mut self, offset: usize) {
+        self.recorded.clear();
+        self.mark = offset;
+    }
+
+    fn text(&self, start: usize, end: usize) -> Cow<'static, str> {
+        Cow::Owned(self.recorded[start - self.mark..end - self.mark].to_string())
+    }
+
+    fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
+
+/// Turns a character source into a stream of tokens, read lazily as the iterator is advanced.
+/// The stream ends after the first error.
+pub struct Lexer<'a, S: CharSource<'a>> {
+    source: S,
+    pos: Pos,
+    done: bool,
+    _input: PhantomData<&'a str>,
+}
+
+impl<'a> Lexer<'a, StrSource<'a>> {
+    pub fn new(input: &'a str) -> Lexer<'a, StrSource<'a>> {
+        Lexer::from_source(StrSource::new(input))
+    }
+}
+
+impl<R: Read> Lexer<'static, ReadSource<R>> {
+    pub fn from_reader(reader: R) -> Lexer<'static, ReadSource<R>> {
+        Lexer::from_source(ReadSource::new(reader))
+    }
+}
+
+impl<'a, S: CharSource<'a>> Lexer<'a, S> {
+    pub fn from_source(source: S) -> Lexer<'a, S> {
+        Lexer {
+            source,
+            pos: Pos::start(),
+            done: false,
+            _input: PhantomData,
+        }
+    }
+
+    /// The position just after the last character consumed.
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.source.peek()
+    }
+
+    fn peek_second(&mut self) -> Option<char> {
+        self.source.peek_second()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.source.bump()?;
+        self.pos = self.pos.advance(c);
+        Some(c)
+    }
+
+    /// Skips whitespace, `;` line comments and `#| ... |#` block comments.
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        loop {
+            // Nothing skipped here is part of a token, so sources need not hold on to it.
+            self.source.mark(self.pos.offset);
+            match (self.peek(), self.peek_second()) {
+                (Some(c), _) if c.is_ascii_whitespace() => {
+                    self.bump();
+                }
+                (Some(';'), _) => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                (Some('#'), Some('|')) => self.block_comment()?,
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Skips a (possibly nested) block comment starting at the current `#|`.
+    fn block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.pos;
+        self.bump();
+        self.bump();
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump() {
+                None => return Err(LexError::UnterminatedComment(Span::new(start, self.pos))),
+                Some('|') if self.peek() == Some('#') => {
+                    self.bump();
+                    depth -= 1;
+                }
+                Some('#') if self.peek() == Some('|') => {
+                    self.bump();
+                    depth += 1;
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the body of an escape sequence; the leading `\` has already been consumed.
+    fn escape(&mut self, start: Pos) -> Result<char, LexError> {
+        let invalid = |lexer: &Self, text: String| {
+            LexError::InvalidEscape(text, Span::new(start, lexer.pos))
+        };
+        match self.bump() {
+            None => Err(LexError::UnterminatedString(Span::new(start, self.pos))),
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('r') => Ok('\r'),
+            Some('0') => Ok('\0'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => {
+                match self.peek() {
+                    Some('{') => {}
+                    None => return Err(LexError::UnterminatedString(Span::new(start, self.pos))),
+                    Some(_) => return Err(invalid(self, "\\u".into())),
+                }
+                self.bump();
+                let mut digits = String::new();
+                loop {
+                    match self.bump() {
+                        Some('}') => break,
+                        Some(c) if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                        // Running out of input is an unfinished string; a `"` ends it too soon.
+                        None => return Err(LexError::UnterminatedString(Span::new(start, self.pos))),
+                        Some('"') => return Err(invalid(self, format!("\\u{{{}", digits))),
+                        Some(c) => return Err(invalid(self, format!("\\u{{{}{}", digits, c))),
+                    }
+                }
+                u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+                    .ok_or_else(|| invalid(self, format!("\\u{{{}}}", digits)))
+            }
+            Some(c) => Err(invalid(self, format!("\\{}", c))),
+        }
+    }
+
+    /// Reads a string literal; the opening `"` has already been consumed. The contents are only
+    /// copied if they contain an escape sequence.
+    fn string(&mut self, start: Pos) -> Result<TokenKind<'a>, LexError> {
+        let contents = self.pos.offset;
+        let mut unescaped: Option<String> = None;
+        loop {
+            let char_start = self.pos;
+            match self.bump() {
+                None => return Err(LexError::UnterminatedString(Span::new(start, self.pos))),
+                Some('"') => {
+                    return Ok(TokenKind::Str(unescaped.map_or_else(
+                        || self.source.text(contents, char_start.offset),
+                        Cow::Owned,
+                    )))
+                }
+                Some('\\') => {
+                    let c = self.escape(char_start)?;
+                    unescaped
+                        .get_or_insert_with(|| self.source.text(contents, char_start.offset).into_owned())
+                        .push(c);
+                }
+                Some(c) if c.is_control() && !c.is_ascii_whitespace() => {
+                    return Err(LexError::InvalidCharacter(c, Span::new(char_start, self.pos)))
+                }
+                Some(c) => {
+                    if let Some(s) = unescaped.as_mut() {
+                        s.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads a character literal; the leading `#\` has already been consumed.
+    fn character(&mut self, start: Pos) -> Result<TokenKind<'a>, LexError> {
+        let char_start = self.pos;
+        let c = match self.bump() {
+            Some(c) => c,
+            None => return Err(LexError::UnknownToken("#\\".into(), Span::new(start, self.pos))),
+        };
+        self.check_char(c, char_start)?;
+        if is_delimiter(c) || self.peek().is_none_or(is_delimiter) {
+            return Ok(TokenKind::Char(c));
+        }
+        let name = self.literal(char_start)?;
+        char_from_name(&name)
+            .map(TokenKind::Char)
+            .ok_or_else(|| LexError::UnknownToken(format!("#\\{}", name), Span::new(start, self.pos)))
+    }
+
+    /// Rejects characters that may not appear outside of strings and comments.
+    fn check_char(&self, c: char, start: Pos) -> Result<(), LexError> {
+        if is_invalid_char(c) {
+            Err(LexError::InvalidCharacter(c, Span::new(start, start.advance(c))))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the rest of a literal that began at `start`.
+    fn literal(&mut self, start: Pos) -> Result<Cow<'a, str>, LexError> {
+        while let Some(c) = self.peek() {
+            if is_delimiter(c) {
+                break;
+            }
+            self.check_char(c, self.pos)?;
+            self.bump();
+        }
+        Ok(self.source.text(start.offset, self.pos.offset))
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token<'a>>, LexError> {
+        self.skip_trivia()?;
+        let start = self.pos;
+        self.source.mark(start.offset);
+        let c = match self.bump() {
+            None => return Ok(None),
+            Some(c) => c,
+        };
+        let kind = match c {
+            '(' => TokenKind::LPar,
+            ')' => TokenKind::RPar,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '"' => self.string(start)?,
+            '#' if self.peek() == Some(';') => {
+                self.bump();
+                TokenKind::DatumComment
+            }
+            '#' if self.peek() == Some('\\') => {
+                self.bump();
+                self.character(start)?
+            }
+            '#' => {
+                let lit = self.literal(start)?;
+                match parse_number(&lit) {
+                    _ if matches!(lit.as_ref(), "#t" | "#true") => TokenKind::Bool(true),
+                    _ if matches!(lit.as_ref(), "#f" | "#false") => TokenKind::Bool(false),
+                    Some(Ok(_)) => TokenKind::Literal(lit),
+                    Some(Err(_)) => {
+                        return Err(LexError::InvalidNumber(lit.into_owned(), Span::new(start, self.pos)))
+                    }
+                    None => return Err(LexError::UnknownToken(lit.into_owned(), Span::new(start, self.pos))),
+                }
+            }
+            '\'' => TokenKind::Quote,
+            '`' => TokenKind::Quasiquote,
+            ',' if self.peek() == Some('@') => {
+                self.bump();
+                TokenKind::UnquoteSplicing
+            }
+            ',' => TokenKind::Unquote,
+            c => {
+                self.check_char(c, start)?;
+                let lit = self.literal(start)?;
+                if let Some(Err(_)) = parse_number(&lit) {
+                    return Err(LexError::InvalidNumber(lit.into_owned(), Span::new(start, self.pos)));
+                }
+                TokenKind::Literal(lit)
+            }
+        };
+        Ok(Some(Token::new(kind, Span::new(start, self.pos))))
+    }
+}
+
+impl<'a, S: CharSource<'a>> Iterator for Lexer<'a, S> {
+    type Item = Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.read_token();
+        // A read error may have cut the token short, so it takes precedence over the token.
+        let result = match self.source.take_error() {
+            Some(err) => Err(LexError::Io(err, Span::point(self.pos))),
+            None => result,
+        };
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+}
+
+/// Lexes the whole of `input`. Literals and strings without escapes borrow from `input`.
+pub fn lex(input: &str) -> Result<Vec<Token<'_>>, LexError> {
+    Lexer::new(input).collect()
+}
+
+/// Lexes everything `reader` produces, reading it incrementally.
+pub fn lex_reader<R: Read>(reader: R) -> Result<Vec<Token<'static>>, LexError> {
+    Lexer::from_reader(reader).collect()
 }
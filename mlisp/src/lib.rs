@@ -1,10 +1,11 @@
-
+pub mod bigint;
+pub mod cst;
 pub mod eval;
+pub mod formatter;
 pub mod interpreter;
 pub mod lex;
+pub mod number;
+pub mod rational;
 pub mod types;
 pub mod parse;
-
-#[cfg(test)]
-mod tests {
-}
+pub mod span;
@@ -0,0 +1,195 @@
+use crate::bigint::BigInt;
+use crate::rational::Rational;
+use crate::types::Expr;
+use std::rc::Rc;
+
+/// A numeric literal as written in the source.
+///
+/// The grammar is:
+/// - integers: `42`, `-7`, `1_000_000`, of any size
+/// - radix integers: `#x1F`, `#o17`, `#b1010`, `#d99`, optionally signed after the prefix (`#x-1F`)
+/// - decimals: `1.5`, `.5`, `1.`, `6.02e23`, `1e-9`
+/// - rationals: `1/2`, `-3/4`, `#x1/A`, read in lowest terms so that `4/2` is the integer `2`
+/// - the special floats `+inf.0`, `-inf.0` and `+nan.0`
+///
+/// `_` may separate digits but may not lead, trail or repeat. Bare `inf`, `nan` and friends are
+/// symbols, not numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    Int(i64),
+    /// An integer outside the range of `i64`. Use `Number::integer` to keep smaller values as
+    /// `Int`.
+    Big(BigInt),
+    Float(f64),
+    /// A fraction that is not an integer. Use `Number::exact` to keep integers as integers.
+    Rational(Rational),
+}
+
+impl Number {
+    /// Converts an exact integer to `Int` if it fits and `Big` otherwise.
+    pub fn integer(n: BigInt) -> Number {
+        n.to_i64().map_or(Number::Big(n), Number::Int)
+    }
+
+    /// Converts an exact fraction to an integer if its denominator is one.
+    pub fn exact(r: Rational) -> Number {
+        if r.is_integer() {
+            Number::integer(r.numerator().clone())
+        } else {
+            Number::Rational(r)
+        }
+    }
+
+    /// Returns the value as a `Rational` if it is exact.
+    pub fn to_rational(&self) -> Option<Rational> {
+        match self {
+            Number::Rational(r) => Some(r.clone()),
+            _ => self.to_bigint().map(Rational::from_integer),
+        }
+    }
+
+    /// Returns the value as a `BigInt` if it is an exact integer.
+    pub fn to_bigint(&self) -> Option<BigInt> {
+        match self {
+            Number::Int(n) => Some(BigInt::from_i64(*n)),
+            Number::Big(n) => Some(n.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Int(n) => n as f64,
+            Number::Big(ref n) => n.to_f64(),
+            Number::Float(f) => f,
+            Number::Rational(ref r) => r.to_f64(),
+        }
+    }
+
+    /// Converts the literal into the value the evaluator works with.
+    pub fn to_expr(self) -> Rc<Expr> {
+        match self {
+            Number::Int(n) => Expr::int(n),
+            Number::Big(n) => Rc::new(Expr::BigInt(n)),
+            Number::Rational(r) => Rc::new(Expr::Rational(r)),
+            Number::Float(f) => Expr::fnum(f),
+        }
+    }
+}
+
+/// Checks whether a literal is meant to be a number: it starts with a digit, a sign or decimal
+/// point followed by a digit, or a radix prefix.
+fn looks_numeric(lit: &str) -> bool {
+    let unsigned = lit.strip_prefix(['+', '-']).unwrap_or(lit);
+    let digits = unsigned.strip_prefix('.').unwrap_or(unsigned);
+    digits.starts_with(|c: char| c.is_ascii_digit()) || radix_prefix(lit).is_some()
+}
+
+fn radix_prefix(lit: &str) -> Option<u32> {
+    let mut chars = lit.chars();
+    if chars.next() != Some('#') {
+        return None;
+    }
+    match chars.next()?.to_ascii_lowercase() {
+        'x' => Some(16),
+        'o' => Some(8),
+        'b' => Some(2),
+        'd' => Some(10),
+        _ => None,
+    }
+}
+
+/// Strips `_` separators from a run of digits, rejecting empty runs, non-digits and misplaced
+/// separators.
+fn digits(s: &str, radix: u32) -> Result<String, String> {
+    if s.is_empty() {
+        return Err("expected digits".into());
+    }
+    if s.starts_with('_') || s.ends_with('_') || s.contains("__") {
+        return Err("digit separators must sit between digits".into());
+    }
+    match s.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+        Some(c) => Err(format!("unexpected {:?} in base {} number", c, radix)),
+        None => Ok(s.replace('_', "")),
+    }
+}
+
+fn parse_int(s: &str, radix: u32) -> Result<Number, String> {
+    parse_integer(s, radix).map(Number::integer)
+}
+
+fn parse_integer(s: &str, radix: u32) -> Result<BigInt, String> {
+    let (negative, unsigned) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let ds = digits(unsigned, radix)?;
+    let signed = if negative { format!("-{}", ds) } else { ds };
+    BigInt::parse(&signed, radix).ok_or_else(|| format!("invalid base {} integer", radix))
+}
+
+fn parse_rational(num: &str, den: &str, radix: u32) -> Result<Number, String> {
+    if den.starts_with(['+', '-']) {
+        return Err("rational denominators cannot be signed".into());
+    }
+    Rational::new(parse_integer(num, radix)?, parse_integer(den, radix)?)
+        .map(Number::exact)
+        .ok_or_else(|| "rational denominator cannot be zero".into())
+}
+
+fn parse_decimal(s: &str) -> Result<Number, String> {
+    let unsigned = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (whole, frac) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+    if exponent.is_none() && frac.is_none() {
+        return parse_int(s, 10);
+    }
+
+    let mut text = String::from(if s.starts_with('-') { "-" } else { "" });
+    match (whole, frac) {
+        ("", Some("")) | ("", None) => return Err("expected digits".into()),
+        (w, Some(f)) => {
+            text += &if w.is_empty() { "0".into() } else { digits(w, 10)? };
+            text += ".";
+            text += &if f.is_empty() { "0".into() } else { digits(f, 10)? };
+        }
+        (w, None) => text += &digits(w, 10)?,
+    }
+    if let Some(exp) = exponent {
+        let unsigned_exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+        text += "e";
+        text += if exp.starts_with('-') { "-" } else { "" };
+        text += &digits(unsigned_exp, 10)?;
+    }
+    text.parse::<f64>().map(Number::Float).map_err(|e| e.to_string())
+}
+
+/// Parses a literal as a number. Returns `None` if the literal is not meant to be a number (and
+/// so is a symbol), or an error describing why a number-like literal is malformed.
+pub fn parse_number(lit: &str) -> Option<Result<Number, String>> {
+    match lit {
+        "+inf.0" => return Some(Ok(Number::Float(f64::INFINITY))),
+        "-inf.0" => return Some(Ok(Number::Float(f64::NEG_INFINITY))),
+        "+nan.0" | "-nan.0" => return Some(Ok(Number::Float(f64::NAN))),
+        _ => {}
+    }
+    if !looks_numeric(lit) {
+        return None;
+    }
+    let (radix, body) = match radix_prefix(lit) {
+        Some(radix) => (radix, &lit[2..]),
+        None => (10, lit),
+    };
+    let parsed = match body.split_once('/') {
+        Some((num, den)) => parse_rational(num, den, radix),
+        None if radix == 10 => parse_decimal(body),
+        None => parse_int(body, radix),
+    };
+    Some(parsed)
+}
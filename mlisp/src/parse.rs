@@ -1,68 +1,482 @@
-use crate::lex::Token;
+use crate::lex::{LexError, Lexer, Token, TokenKind};
+use crate::number::parse_number;
+use crate::span::Span;
 use crate::types::Expr;
+use std::fmt;
 use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum ParseError {
-    BadParse(String),
-    EOF,
+    BadParse(String, Span),
+    EOF(Span),
+}
+
+impl ParseError {
+    /// The region of source the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::BadParse(_, span) | ParseError::EOF(span) => *span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::BadParse(msg, span) => write!(f, "{}: {}", span, msg),
+            ParseError::EOF(span) => write!(f, "{}: unexpected end of input", span),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum ParseResult {
     Success(usize, Rc<Expr>),
     Failure(ParseError),
+    /// The tokens ran out partway through a form, so more input could still complete it.
+    Incomplete(ParseError),
+}
+
+impl ParseResult {
+    fn into_error(self) -> ParseError {
+        match self {
+            ParseResult::Failure(err) | ParseResult::Incomplete(err) => err,
+            ParseResult::Success(..) => unreachable!("into_error called on a successful parse"),
+        }
+    }
+}
+
+/// Whether some source text is ready to be evaluated, as judged by `check_input`.
+#[derive(Debug)]
+pub enum InputStatus {
+    /// The input is zero or more complete forms.
+    Complete,
+    /// The input is valid so far, but ends partway through a form, string, character literal or
+    /// block comment.
+    Incomplete,
+    /// The input has a lexical error that no further input could fix.
+    LexError(LexError),
+    /// The input has a syntax error that no further input could fix.
+    ParseError(ParseError),
+}
+
+/// Checks whether `source` holds complete forms, without evaluating it. Unlike `parse_program`,
+/// this tells input that merely stops too soon (an open delimiter, string, character literal or
+/// block comment) apart from input that is wrong, so that a REPL can keep reading lines until a
+/// form is finished.
+pub fn check_input(source: &str) -> InputStatus {
+    let mut tokens = Vec::new();
+    let mut unterminated = false;
+    for token in Lexer::new(source) {
+        match token {
+            Ok(token) => tokens.push(token),
+            Err(LexError::UnterminatedString(_) | LexError::UnterminatedComment(_)) => unterminated = true,
+            // A character literal cut off by the end of input, such as `#\` or `#\spa`.
+            Err(LexError::UnknownToken(text, span)) if text.starts_with("#\\") && span.end.offset == source.len() => {
+                unterminated = true
+            }
+            Err(err) => return InputStatus::LexError(err),
+        }
+    }
+    // The tokens before an unterminated literal can still be wrong on their own.
+    match read_program(&tokens, ParseOptions::default()) {
+        Ok(_) if !unterminated => InputStatus::Complete,
+        Ok(_) | Err(ParseResult::Incomplete(_)) => InputStatus::Incomplete,
+        Err(result) => InputStatus::ParseError(result.into_error()),
+    }
+}
+
+/// Limits applied while parsing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// How deeply sequences and quote shorthands may nest before parsing fails with an error, or
+    /// `None` for no limit beyond available memory.
+    pub max_depth: Option<usize>,
 }
 
+/// Parses the first form in `tokens`. Anything after it is ignored; use `parse_program` to read a
+/// whole source file.
 pub fn parse(tokens: &[Token]) -> Result<Rc<Expr>, ParseError> {
-    match parser(tokens, 0) {
-    	ParseResult::Success(_, expr) => Ok(expr),
-    	ParseResult::Failure(err) => Err(err),
-    }
-}
-
-fn parser(tokens: &[Token], index: usize) -> ParseResult {
-	let mut index = index;
-	if let Some(mut t) = tokens.get(index) {
-		match &*t {
-			Token::LPar => {
-				index += 1;
-				let mut exprs = Vec::new();
-
-				while *t !=Token::RPar {
-					match parser(tokens, index) {
-						ParseResult::Success(idx, expr) => {
-							exprs.push(expr);
-							index = idx;
-						},
-						e => return e,
-					}
-					if index >= tokens.len() {
-						return ParseResult::Failure(ParseError::BadParse("Unclosed delimeter".into()));
-					}
-					t = &tokens[index];
-				}
-				ParseResult::Success(index + 1, Expr::list(&exprs))
-			},
-			Token::RPar => {
-				ParseResult::Failure(ParseError::BadParse("Unexpected ) encountered.".to_string()))
-			},
-			Token::Literal(s) => {
-				if let Ok(n) = s.parse::<f64>() {
-					ParseResult::Success(index + 1, Expr::fnum(n))
-				} else {
-					ParseResult::Success(index + 1, Expr::symbol(&s))
-				}
-			},
-			_ => ParseResult::Failure(ParseError::BadParse(format!("Unknown token: {:?}", *t))),
-		}
-
-	} else {
-		ParseResult::Failure(ParseError::EOF)
-	}
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
+    parse_with(tokens, ParseOptions::default())
+}
+
+/// Like `parse`, with the given limits.
+pub fn parse_with(tokens: &[Token], options: ParseOptions) -> Result<Rc<Expr>, ParseError> {
+    match parser(tokens, 0, options) {
+        ParseResult::Success(_, expr) => Ok(expr),
+        result => Err(result.into_error()),
+    }
+}
+
+/// Parses every top-level form in `tokens`, in order. Fails if any tokens are left over that do
+/// not form a complete datum, such as a stray closing delimiter.
+pub fn parse_program(tokens: &[Token]) -> Result<Vec<Rc<Expr>>, ParseError> {
+    parse_program_with(tokens, ParseOptions::default())
+}
+
+/// Like `parse_program`, with the given limits.
+pub fn parse_program_with(tokens: &[Token], options: ParseOptions) -> Result<Vec<Rc<Expr>>, ParseError> {
+    read_program(tokens, options).map_err(ParseResult::into_error)
+}
+
+/// Reads every top-level form in `tokens`. On error, returns the `Failure` or `Incomplete` result
+/// that stopped it.
+fn read_program(tokens: &[Token], options: ParseOptions) -> Result<Vec<Rc<Expr>>, ParseResult> {
+    let mut forms = Vec::new();
+    let mut index = 0;
+    loop {
+        index = skip_datum_comments(tokens, index, options)?;
+        if index >= tokens.len() {
+            return Ok(forms);
+        }
+        match parser(tokens, index, options) {
+            ParseResult::Success(idx, expr) => {
+                forms.push(expr);
+                index = idx;
+            }
+            result => return Err(result),
+        }
+    }
+}
+
+/// Parses every top-level form in `tokens` like `parse_program`, but keeps going after an error
+/// so that all of them can be reported at once. Returns the forms that could be read alongside
+/// the diagnostics, in source order.
+///
+/// Delimiter problems are repaired so the rest of a broken form can still be read: a mismatched
+/// closer closes the opener it does match (or is dropped if it matches none), and a form left
+/// unclosed at the end of input ends just before the next `(` in the first column. Other errors,
+/// such as a malformed number, drop the form they occur in.
+pub fn parse_program_recovering(tokens: &[Token]) -> (Vec<Rc<Expr>>, Vec<ParseError>) {
+    let mut forms = Vec::new();
+    let mut errors = Vec::new();
+    let mut index = 0;
+    while let Some(t) = tokens.get(index) {
+        if is_closer(&t.kind) {
+            errors.push(unexpected(t));
+            index += 1;
+            continue;
+        }
+        let mut form = scan_form(tokens, index, tokens.len());
+        if form.unclosed {
+            let next_form = (index + 1..tokens.len())
+                .find(|&i| closer(&tokens[i].kind).is_some() && tokens[i].span.start.column == 1);
+            if let Some(limit) = next_form {
+                form = scan_form(tokens, index, limit);
+            }
+        }
+        errors.append(&mut form.errors);
+        match parse_program(&form.tokens) {
+            Ok(mut exprs) => forms.append(&mut exprs),
+            Err(err) => errors.push(err),
+        }
+        index = form.end;
+    }
+    (forms, errors)
+}
+
+/// A top-level form found by `scan_form`, with its delimiters repaired so that it can be parsed.
+struct ScannedForm<'a> {
+    /// The index just past the form.
+    end: usize,
+    tokens: Vec<Token<'a>>,
+    errors: Vec<ParseError>,
+    unclosed: bool,
+}
+
+/// Finds the extent of the form starting at `index` by matching delimiters, stopping at `limit`.
+/// Besides the form itself this takes in any reader shorthands and `#;` datum comments before it.
+fn scan_form<'a>(tokens: &[Token<'a>], index: usize, limit: usize) -> ScannedForm<'a> {
+    let mut open: Vec<&Token> = Vec::new();
+    let mut repaired = Vec::new();
+    let mut errors = Vec::new();
+    // Openers already reported as mismatched, so they aren't reported again as unclosed.
+    let mut mismatched_openers = Vec::new();
+    // Each datum comment at the top of the form calls for one more datum to follow it.
+    let mut datums_needed = 1;
+    let mut index = index;
+
+    while index < limit && datums_needed > 0 {
+        let t = &tokens[index];
+        if is_closer(&t.kind) {
+            let Some(top) = open.last() else { break };
+            if closer(&top.kind) != Some(t.kind.clone()) {
+                errors.push(mismatched(top, t));
+                mismatched_openers.push(top.span);
+                match open.iter().rposition(|o| closer(&o.kind) == Some(t.kind.clone())) {
+                    // Everything opened since the matching opener is closed along with it.
+                    Some(depth) => {
+                        for o in open.drain(depth + 1..).rev() {
+                            repaired.push(Token::new(closer(&o.kind).unwrap(), Span::point(t.span.start)));
+                        }
+                    }
+                    None => {
+                        index += 1;
+                        continue;
+                    }
+                }
+            }
+            open.pop();
+        } else if closer(&t.kind).is_some() {
+            open.push(t);
+        }
+        repaired.push(t.clone());
+        index += 1;
+
+        if open.is_empty() {
+            match t.kind {
+                TokenKind::DatumComment => datums_needed += 1,
+                TokenKind::Quote | TokenKind::Quasiquote | TokenKind::Unquote | TokenKind::UnquoteSplicing => {}
+                _ => datums_needed -= 1,
+            }
+        }
+    }
+
+    let is_unclosed = !open.is_empty();
+    if let Some(innermost) = open.last() {
+        if !mismatched_openers.contains(&innermost.span) {
+            errors.push(unclosed(innermost));
+        }
+        let end = Span::point(tokens[index - 1].span.end);
+        for o in open.iter().rev() {
+            repaired.push(Token::new(closer(&o.kind).unwrap(), end));
+        }
+    }
+    ScannedForm { end: index, tokens: repaired, errors, unclosed: is_unclosed }
+}
+
+/// The (empty) span just past the last token, used when input runs out.
+fn end_of_input(tokens: &[Token]) -> Span {
+    tokens
+        .last()
+        .map_or_else(Span::default, |t| Span::point(t.span.end))
+}
+
+/// Skips any `#;` datum comments at `index`, along with the datums they comment out.
+fn skip_datum_comments(tokens: &[Token], index: usize, options: ParseOptions) -> Result<usize, ParseResult> {
+    let mut index = index;
+    while let Some(TokenKind::DatumComment) = tokens.get(index).map(|t| &t.kind) {
+        match parser(tokens, index + 1, options) {
+            ParseResult::Success(idx, _) => index = idx,
+            result => return Err(result),
+        }
+    }
+    Ok(index)
+}
+
+/// The closing delimiter matching an opening one, or `None` if `kind` does not open a sequence.
+pub(crate) fn closer(kind: &TokenKind) -> Option<TokenKind<'static>> {
+    match kind {
+        TokenKind::LPar => Some(TokenKind::RPar),
+        TokenKind::LBracket => Some(TokenKind::RBracket),
+        TokenKind::LBrace => Some(TokenKind::RBrace),
+        _ => None,
+    }
+}
+
+fn delimiter_str(kind: &TokenKind) -> &'static str {
+    match kind {
+        TokenKind::LPar => "(",
+        TokenKind::RPar => ")",
+        TokenKind::LBracket => "[",
+        TokenKind::RBracket => "]",
+        TokenKind::LBrace => "{",
+        TokenKind::RBrace => "}",
+        _ => "",
+    }
+}
+
+pub(crate) fn is_closer(kind: &TokenKind) -> bool {
+    matches!(kind, TokenKind::RPar | TokenKind::RBracket | TokenKind::RBrace)
+}
+
+pub(crate) fn unclosed(open: &Token) -> ParseError {
+    ParseError::BadParse(
+        format!(
+            "Unclosed delimiter: {} opened at {} is never closed",
+            delimiter_str(&open.kind),
+            open.span
+        ),
+        open.span,
+    )
+}
+
+pub(crate) fn mismatched(open: &Token, found: &Token) -> ParseError {
+    let expected = closer(&open.kind).expect("mismatched called on an opening delimiter");
+    ParseError::BadParse(
+        format!(
+            "Mismatched delimiter: expected {} but found {} ({} opened at {})",
+            delimiter_str(&expected),
+            delimiter_str(&found.kind),
+            delimiter_str(&open.kind),
+            open.span
+        ),
+        found.span,
+    )
+}
+
+pub(crate) fn unexpected(found: &Token) -> ParseError {
+    ParseError::BadParse(
+        format!("Unexpected {} encountered.", delimiter_str(&found.kind)),
+        found.span,
+    )
+}
+
+/// Pairs up the datums of a map literal, rejecting odd lengths and duplicate keys.
+fn build_map(exprs: Vec<Rc<Expr>>, open: Span) -> Result<Rc<Expr>, ParseError> {
+    if !exprs.len().is_multiple_of(2) {
+        return Err(ParseError::BadParse(
+            "Map literals must contain an even number of forms, as {key value ...}".into(),
+            open,
+        ));
+    }
+    let mut kvs: Vec<(Rc<Expr>, Rc<Expr>)> = Vec::new();
+    for pair in exprs.chunks(2) {
+        if kvs.iter().any(|(k, _)| *k == pair[0]) {
+            return Err(ParseError::BadParse(
+                format!("Duplicate key {:?} in map literal", pair[0]),
+                open,
+            ));
+        }
+        kvs.push((pair[0].clone(), pair[1].clone()));
+    }
+    Ok(Expr::map(&kvs))
+}
+
+/// A datum that is still being read. These are kept on an explicit stack rather than the call
+/// stack, so nesting depth is limited only by memory.
+enum Frame<'t, 'a> {
+    /// A sequence opened by `open`, along with the datums read so far. In a dotted list, `dot` is
+    /// the `.` and `tail` the datum after it.
+    Seq {
+        open: &'t Token<'a>,
+        exprs: Vec<Rc<Expr>>,
+        dot: Option<&'t Token<'a>>,
+        tail: Option<Rc<Expr>>,
+    },
+    /// A reader shorthand waiting for its datum, which it wraps as `(name datum)`.
+    Quoted(&'static str),
+    /// A `#;` datum comment waiting for the datum it discards.
+    Comment,
+}
+
+fn parser(tokens: &[Token], index: usize, options: ParseOptions) -> ParseResult {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut index = index;
+    loop {
+        let Some(t) = tokens.get(index) else {
+            return ParseResult::Incomplete(match stack.last() {
+                Some(Frame::Seq { open, .. }) => unclosed(open),
+                _ => ParseError::EOF(end_of_input(tokens)),
+            });
+        };
+        index += 1;
+
+        // Only the closing delimiter (or datum comments) may follow the tail of a dotted list.
+        if let Some(Frame::Seq { tail: Some(_), .. }) = stack.last() {
+            if !is_closer(&t.kind) && t.kind != TokenKind::DatumComment {
+                return ParseResult::Failure(ParseError::BadParse(
+                    "Only one datum may follow . in a dotted list".into(),
+                    t.span,
+                ));
+            }
+        }
+        if matches!(&t.kind, TokenKind::Literal(s) if s == ".") {
+            match stack.last_mut() {
+                Some(Frame::Seq { open, exprs, dot: dot @ None, .. })
+                    if open.kind == TokenKind::LPar && !exprs.is_empty() =>
+                {
+                    *dot = Some(t);
+                    continue;
+                }
+                _ => return ParseResult::Failure(ParseError::BadParse("Unexpected . encountered.".into(), t.span)),
+            }
+        }
+
+        let frame = match &t.kind {
+            TokenKind::LPar | TokenKind::LBracket | TokenKind::LBrace => Some(Frame::Seq {
+                open: t,
+                exprs: Vec::new(),
+                dot: None,
+                tail: None,
+            }),
+            TokenKind::DatumComment => Some(Frame::Comment),
+            TokenKind::Quote => Some(Frame::Quoted("quote")),
+            TokenKind::Quasiquote => Some(Frame::Quoted("quasiquote")),
+            TokenKind::Unquote => Some(Frame::Quoted("unquote")),
+            TokenKind::UnquoteSplicing => Some(Frame::Quoted("unquote-splicing")),
+            _ => None,
+        };
+        if let Some(frame) = frame {
+            if let Some(max) = options.max_depth.filter(|&max| stack.len() >= max) {
+                return ParseResult::Failure(ParseError::BadParse(
+                    format!("Nesting exceeds the maximum depth of {}", max),
+                    t.span,
+                ));
+            }
+            stack.push(frame);
+            continue;
+        }
+
+        let mut datum = match &t.kind {
+            TokenKind::RPar | TokenKind::RBracket | TokenKind::RBrace => match stack.pop() {
+                Some(Frame::Seq { open, exprs, dot, tail }) if closer(&open.kind).as_ref() == Some(&t.kind) => {
+                    match (&open.kind, dot, tail) {
+                        (_, Some(dot), None) => {
+                            return ParseResult::Failure(ParseError::BadParse(
+                                "Expected a datum after . in a dotted list".into(),
+                                dot.span,
+                            ))
+                        }
+                        (_, _, Some(tail)) => Expr::dotted(&exprs, tail),
+                        (TokenKind::LPar, _, _) => Expr::list(&exprs),
+                        (TokenKind::LBracket, _, _) => Expr::vector(&exprs),
+                        _ => match build_map(exprs, open.span) {
+                            Ok(map) => map,
+                            Err(err) => return ParseResult::Failure(err),
+                        },
+                    }
+                }
+                Some(Frame::Seq { open, .. }) => return ParseResult::Failure(mismatched(open, t)),
+                _ => return ParseResult::Failure(unexpected(t)),
+            },
+            TokenKind::Str(s) => Expr::string(s),
+            TokenKind::Char(c) => Expr::character(*c),
+            TokenKind::Bool(b) => Expr::boolean(*b),
+            TokenKind::Literal(s) => match parse_number(s) {
+                Some(Ok(n)) => n.to_expr(),
+                Some(Err(msg)) => {
+                    return ParseResult::Failure(ParseError::BadParse(
+                        format!("Invalid number literal {}: {}", s, msg),
+                        t.span,
+                    ))
+                }
+                None => Expr::symbol(s),
+            },
+            _ => unreachable!("tokens that open a frame are handled above"),
+        };
+
+        // Hand the finished datum to whatever is waiting on it.
+        loop {
+            match stack.last_mut() {
+                None => return ParseResult::Success(index, datum),
+                Some(Frame::Seq { dot: Some(_), tail, .. }) => {
+                    *tail = Some(datum);
+                    break;
+                }
+                Some(Frame::Seq { exprs, .. }) => {
+                    exprs.push(datum);
+                    break;
+                }
+                Some(Frame::Quoted(name)) => {
+                    datum = Expr::list(&[Expr::symbol(name), datum]);
+                    stack.pop();
+                }
+                Some(Frame::Comment) => {
+                    stack.pop();
+                    break;
+                }
+            }
+        }
+    }
 }
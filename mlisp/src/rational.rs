@@ -0,0 +1,181 @@
+use crate::bigint::BigInt;
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+/// An exact fraction, always in lowest terms with a positive denominator, so equal values always
+/// have equal representations. Integers are fractions with a denominator of one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+impl Rational {
+    /// Builds `numerator / denominator` in lowest terms. Returns `None` if the denominator is zero.
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Option<Rational> {
+        if denominator.is_zero() {
+            return None;
+        }
+        let divisor = numerator.gcd(&denominator);
+        let divisor = if denominator.is_negative() { -&divisor } else { divisor };
+        let (numerator, _) = numerator.div_rem(&divisor)?;
+        let (denominator, _) = denominator.div_rem(&divisor)?;
+        Some(Rational { numerator, denominator })
+    }
+
+    pub fn from_integer(n: BigInt) -> Rational {
+        Rational { numerator: n, denominator: BigInt::from_i64(1) }
+    }
+
+    /// The exact value of a finite float, so `0.1` is the fraction it actually stores rather than
+    /// `1/10`. Returns `None` for NaN and the infinities.
+    pub fn from_f64(f: f64) -> Option<Rational> {
+        if !f.is_finite() {
+            return None;
+        }
+        let bits = f.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = (bits & ((1 << 52) - 1)) as i64;
+        // Subnormals have no implicit leading one; both are scaled so the mantissa is an integer.
+        let (mantissa, exponent) = if exponent == 0 { (fraction, -1074) } else { (fraction | 1 << 52, exponent - 1075) };
+        let mantissa = BigInt::from_i64(if f.is_sign_negative() { -mantissa } else { mantissa });
+        if exponent >= 0 {
+            Some(Rational::from_integer(&mantissa << exponent as usize))
+        } else {
+            Rational::new(mantissa, &BigInt::from_i64(1) << -exponent as usize)
+        }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn denominator(&self) -> &BigInt {
+        &self.denominator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator == BigInt::from_i64(1)
+    }
+
+    /// Returns the nearest `f64`.
+    pub fn to_f64(&self) -> f64 {
+        let (n, d) = (self.numerator.to_f64(), self.denominator.to_f64());
+        if n.is_finite() && d.is_finite() {
+            return n / d;
+        }
+        // Too large to convert the parts separately: scale the numerator or denominator by a power
+        // of two so the integer quotient keeps 64 significant bits, then scale the result back.
+        let shift = self.denominator.bits() as i64 - self.numerator.bits() as i64 + 64;
+        let quotient = if shift >= 0 {
+            (&self.numerator << shift as usize).div_rem(&self.denominator)
+        } else {
+            self.numerator.div_rem(&(&self.denominator << -shift as usize))
+        };
+        quotient.map_or(f64::NAN, |(q, _)| q.to_f64() * 2f64.powi(-shift.clamp(-2000, 2000) as i32))
+    }
+
+    /// Returns `None` when dividing by zero.
+    pub fn checked_div(&self, other: &Rational) -> Option<Rational> {
+        Rational::new(&self.numerator * &other.denominator, &self.denominator * &other.numerator)
+    }
+
+    /// `numerator` over the product of both denominators.
+    fn with_denominator(&self, numerator: BigInt, other: &Rational) -> Rational {
+        let denominator = &self.denominator * &other.denominator;
+        Rational::new(numerator, denominator).expect("denominators are never zero")
+    }
+}
+
+impl Add for &Rational {
+    type Output = Rational;
+
+    fn add(self, other: &Rational) -> Rational {
+        let numerator = &(&self.numerator * &other.denominator) + &(&other.numerator * &self.denominator);
+        self.with_denominator(numerator, other)
+    }
+}
+
+impl Sub for &Rational {
+    type Output = Rational;
+
+    fn sub(self, other: &Rational) -> Rational {
+        let numerator = &(&self.numerator * &other.denominator) - &(&other.numerator * &self.denominator);
+        self.with_denominator(numerator, other)
+    }
+}
+
+impl Mul for &Rational {
+    type Output = Rational;
+
+    fn mul(self, other: &Rational) -> Rational {
+        self.with_denominator(&self.numerator * &other.numerator, other)
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ratio(n: i64, d: i64) -> Rational {
+        Rational::new(BigInt::from_i64(n), BigInt::from_i64(d)).unwrap()
+    }
+
+    #[test]
+    fn normalises_to_lowest_terms() {
+        assert_eq!("1/2", ratio(2, 4).to_string());
+        assert_eq!("-3/4", ratio(6, -8).to_string());
+        assert_eq!("3", ratio(-9, -3).to_string());
+        assert_eq!("0", ratio(0, -5).to_string());
+        assert_eq!(ratio(0, 1), ratio(0, 7));
+        assert_eq!(None, Rational::new(BigInt::from_i64(1), BigInt::zero()));
+    }
+
+    #[test]
+    fn arithmetic_is_exact() {
+        let third = ratio(1, 3);
+        assert_eq!(ratio(1, 1), &(&third + &third) + &third);
+        assert_eq!(ratio(1, 6), &ratio(1, 2) - &third);
+        assert_eq!(ratio(-1, 6), &ratio(1, 2) * &ratio(-1, 3));
+        assert_eq!(Some(ratio(3, 2)), ratio(1, 2).checked_div(&third));
+        assert_eq!(None, third.checked_div(&ratio(0, 1)));
+    }
+
+    #[test]
+    fn converts_floats_exactly() {
+        assert_eq!(Some(ratio(1, 2)), Rational::from_f64(0.5));
+        assert_eq!(Some(ratio(-3, 1)), Rational::from_f64(-3.0));
+        assert_eq!(Some(ratio(0, 1)), Rational::from_f64(-0.0));
+        assert_eq!("3602879701896397/36028797018963968", Rational::from_f64(0.1).unwrap().to_string());
+        assert_eq!(Some(1e300), Rational::from_f64(1e300).map(|r| r.to_f64()));
+        let subnormal = Rational::new(BigInt::from_i64(1), &BigInt::from_i64(1) << 1024);
+        assert_eq!(subnormal, Rational::from_f64(f64::MIN_POSITIVE / 4.0));
+        assert_eq!(None, Rational::from_f64(f64::NAN));
+        assert_eq!(None, Rational::from_f64(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn converts_to_the_nearest_float() {
+        assert_eq!(0.5, ratio(1, 2).to_f64());
+        assert_eq!(1.0 / 3.0, ratio(1, 3).to_f64());
+        let huge = BigInt::parse(&format!("1{}", "0".repeat(400)), 10).unwrap();
+        let r = Rational::new(&huge + &BigInt::from_i64(1), huge.clone()).unwrap();
+        assert_eq!(1.0, r.to_f64());
+        let r = Rational::new(-&(&huge * &BigInt::from_i64(3)), &(&huge * &BigInt::from_i64(2)) + &BigInt::from_i64(1)).unwrap();
+        assert_eq!(-1.5, r.to_f64());
+        let r = Rational::new(BigInt::from_i64(1), &huge * &BigInt::from_i64(3)).unwrap();
+        assert_eq!(0.0, r.to_f64());
+        let r = Rational::new(&huge * &huge, BigInt::from_i64(3)).unwrap();
+        assert_eq!(f64::INFINITY, r.to_f64());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::ops::Range;
+
+/// A location in the source text. `line` and `column` are 1-based, `offset` is a byte offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pos {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Pos {
+    pub fn new(offset: usize, line: usize, column: usize) -> Pos {
+        Pos { offset, line, column }
+    }
+
+    /// The position of the first character of a source file.
+    pub fn start() -> Pos {
+        Pos::new(0, 1, 1)
+    }
+
+    /// Returns the position just after `c`, assuming `c` sits at `self`.
+    pub fn advance(self, c: char) -> Pos {
+        if c == '\n' {
+            Pos::new(self.offset + c.len_utf8(), self.line + 1, 1)
+        } else {
+            Pos::new(self.offset + c.len_utf8(), self.line, self.column + 1)
+        }
+    }
+}
+
+impl Default for Pos {
+    fn default() -> Pos {
+        Pos::start()
+    }
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A half-open range `[start, end)` of the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl Span {
+    pub fn new(start: Pos, end: Pos) -> Span {
+        Span { start, end }
+    }
+
+    /// An empty span sitting at `pos`.
+    pub fn point(pos: Pos) -> Span {
+        Span::new(pos, pos)
+    }
+
+    /// The smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+
+    /// The byte offsets the span covers, for slicing the source text.
+    pub fn range(self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn advance_tracks_lines_and_columns() {
+        let p = Pos::start().advance('a').advance('\n').advance('b');
+        assert_eq!(Pos::new(3, 2, 2), p);
+    }
+
+    #[test]
+    fn advance_counts_bytes() {
+        let p = Pos::start().advance('λ');
+        assert_eq!(Pos::new(2, 1, 2), p);
+    }
+}
@@ -1,19 +1,131 @@
+use crate::bigint::BigInt;
+use crate::eval::{Context, EvalResult};
+use crate::rational::Rational;
+use std::fmt;
+use std::mem;
 use std::rc::Rc;
 
 
 #[derive(Debug)]
 pub enum Expr {
     Symbol(String),
+    /// An exact integer.
+    Int(i64),
+    /// An exact integer outside the range of `i64`. Build these with `Expr::integer`, which keeps
+    /// smaller values as `Int`.
+    BigInt(BigInt),
+    /// An exact fraction that is not an integer, such as `1/3`. Build these with
+    /// `Expr::rational`, which keeps integers as `Int` or `BigInt`.
+    Rational(Rational),
+    /// An inexact number. Arithmetic mixing exact numbers and `FNum` produces an `FNum`.
     FNum(f64),
+    Str(String),
+    Char(char),
+    Bool(bool),
     List(Vec<Rc<Expr>>),
+    /// An improper list such as `(a b . c)`: one or more elements followed by a tail that is not
+    /// a list. Build these with `Expr::dotted`, which keeps proper lists as `List`s.
+    DottedList(Vec<Rc<Expr>>, Rc<Expr>),
+    Vector(Vec<Rc<Expr>>),
+    /// Key/value pairs in the order they were written. Keys are unique.
+    Map(Vec<(Rc<Expr>, Rc<Expr>)>),
+    /// A function value, as made by `lambda` or `fn`.
+    Lambda(Lambda),
+    /// A function implemented in Rust, such as the builtins or those added with
+    /// `Environment::register_native`.
+    Native(Native),
+}
+
+/// A closure: the parameters and body of a function along with the contexts it was made in.
+/// Calling it binds the arguments to `params` in a new context on top of `env` and evaluates
+/// `body` there, so the body sees the variables around the `lambda` rather than the caller's.
+pub struct Lambda {
+    pub params: Vec<String>,
+    /// The name after the `.` in `(a b . rest)`, bound to a list of any arguments past `params`.
+    pub rest: Option<String>,
+    pub body: Rc<Expr>,
+    /// The contexts in scope where the function was made, innermost last.
+    pub env: Vec<Context>,
+}
+
+impl Lambda {
+    pub fn arity(&self) -> Arity {
+        if self.rest.is_some() {
+            Arity::AtLeast(self.params.len())
+        } else {
+            Arity::Exactly(self.params.len())
+        }
+    }
+}
+
+/// Leaves out the captured contexts, which usually contain the function itself.
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lambda")
+            .field("params", &self.params)
+            .field("rest", &self.rest)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The body of a native function. It is given its arguments already evaluated, and may return
+/// `EvalResult::Unit`, as `print` does.
+pub type NativeFn = dyn Fn(&[Rc<Expr>]) -> EvalResult;
+
+pub struct Native {
+    /// The name the function was registered under, used when printing it.
+    pub name: String,
+    pub arity: Arity,
+    pub func: Rc<NativeFn>,
+}
+
+impl fmt::Debug for Native {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Native")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// How many arguments a function takes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    /// Checks that a call passing `n` arguments is allowed.
+    pub fn check(self, n: usize) -> Result<(), String> {
+        match self {
+            Arity::Exactly(k) if n != k => Err(format!("Provided {} arguments but expected {}", n, k)),
+            Arity::AtLeast(k) if n < k => Err(format!("Provided {} arguments but expected at least {}", n, k)),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl PartialEq for Expr {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Expr::Symbol(s1), Expr::Symbol(s2)) => s1 == s2,
-            (Expr::FNum(n1), Expr::FNum(n2)) => (n1 - n2).abs() <= 1e-8,
+            (Expr::Int(n1), Expr::Int(n2)) => n1 == n2,
+            (Expr::BigInt(n1), Expr::BigInt(n2)) => n1 == n2,
+            (Expr::Rational(r1), Expr::Rational(r2)) => r1 == r2,
+            (Expr::FNum(n1), Expr::FNum(n2)) => n1 == n2 || (n1 - n2).abs() <= 1e-8,
+            (Expr::Str(s1), Expr::Str(s2)) => s1 == s2,
+            (Expr::Char(c1), Expr::Char(c2)) => c1 == c2,
+            (Expr::Bool(b1), Expr::Bool(b2)) => b1 == b2,
             (Expr::List(xs1), Expr::List(xs2)) => xs1 == xs2,
+            (Expr::DottedList(xs1, t1), Expr::DottedList(xs2, t2)) => xs1 == xs2 && t1 == t2,
+            (Expr::Vector(xs1), Expr::Vector(xs2)) => xs1 == xs2,
+            (Expr::Map(kvs1), Expr::Map(kvs2)) => {
+                kvs1.len() == kvs2.len() && kvs1.iter().all(|kv| kvs2.contains(kv))
+            }
+            // Functions can't be compared by what they compute, so each is only equal to itself.
+            (Expr::Lambda(_), Expr::Lambda(_)) | (Expr::Native(_), Expr::Native(_)) => std::ptr::eq(self, other),
             _ => false,
         }
     }
@@ -21,19 +133,303 @@ impl PartialEq for Expr {
 
 impl Eq for Expr {}
 
+/// Children are dropped with an explicit worklist rather than recursively, so that dropping deeply
+/// nested data doesn't overflow the stack.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut pending = self.take_children();
+        while let Some(child) = pending.pop() {
+            if let Ok(mut expr) = Rc::try_unwrap(child) {
+                pending.append(&mut expr.take_children());
+            }
+        }
+    }
+}
+
 impl Expr {
     pub fn symbol(s: &str) -> Rc<Expr> {
         Rc::new(Expr::Symbol(s.to_string()))
     }
 
+    pub fn int(n: i64) -> Rc<Expr> {
+        Rc::new(Expr::Int(n))
+    }
+
+    pub fn integer(n: BigInt) -> Rc<Expr> {
+        Rc::new(n.to_i64().map_or(Expr::BigInt(n), Expr::Int))
+    }
+
+    pub fn rational(r: Rational) -> Rc<Expr> {
+        if r.is_integer() {
+            Expr::integer(r.numerator().clone())
+        } else {
+            Rc::new(Expr::Rational(r))
+        }
+    }
+
     pub fn fnum(n: f64) -> Rc<Expr> {
         Rc::new(Expr::FNum(n))
     }
 
+    pub fn string(s: &str) -> Rc<Expr> {
+        Rc::new(Expr::Str(s.to_string()))
+    }
+
+    pub fn character(c: char) -> Rc<Expr> {
+        Rc::new(Expr::Char(c))
+    }
+
+    pub fn boolean(b: bool) -> Rc<Expr> {
+        Rc::new(Expr::Bool(b))
+    }
+
     pub fn list(xs: &[Rc<Expr>]) -> Rc<Expr> {
-        Rc::new(Expr::List(xs.iter().cloned().collect()))
+        Rc::new(Expr::List(xs.to_vec()))
+    }
+
+    /// Builds `(xs . tail)`. A list tail is joined onto `xs`, so `(a . (b c))` is the proper list
+    /// `(a b c)` and `(a . (b . c))` is `(a b . c)`.
+    pub fn dotted(xs: &[Rc<Expr>], tail: Rc<Expr>) -> Rc<Expr> {
+        let mut elements = xs.to_vec();
+        match &*tail {
+            Expr::List(rest) => {
+                elements.extend(rest.iter().cloned());
+                Rc::new(Expr::List(elements))
+            }
+            Expr::DottedList(rest, last) => {
+                elements.extend(rest.iter().cloned());
+                Rc::new(Expr::DottedList(elements, last.clone()))
+            }
+            _ if elements.is_empty() => tail,
+            _ => Rc::new(Expr::DottedList(elements, tail)),
+        }
+    }
+
+    pub fn vector(xs: &[Rc<Expr>]) -> Rc<Expr> {
+        Rc::new(Expr::Vector(xs.to_vec()))
+    }
+
+    pub fn lambda(params: &[String], rest: Option<&str>, body: Rc<Expr>, env: &[Context]) -> Rc<Expr> {
+        Rc::new(Expr::Lambda(Lambda {
+            params: params.to_vec(),
+            rest: rest.map(str::to_string),
+            body,
+            env: env.to_vec(),
+        }))
+    }
+
+    pub fn native(name: &str, arity: Arity, func: impl Fn(&[Rc<Expr>]) -> EvalResult + 'static) -> Rc<Expr> {
+        Rc::new(Expr::Native(Native {
+            name: name.to_string(),
+            arity,
+            func: Rc::new(func),
+        }))
+    }
+
+    /// Builds a map from key/value pairs, with later pairs replacing earlier ones with equal keys.
+    pub fn map(kvs: &[(Rc<Expr>, Rc<Expr>)]) -> Rc<Expr> {
+        let mut entries: Vec<(Rc<Expr>, Rc<Expr>)> = Vec::new();
+        for (k, v) in kvs {
+            match entries.iter_mut().find(|(key, _)| key == k) {
+                Some(entry) => entry.1 = v.clone(),
+                None => entries.push((k.clone(), v.clone())),
+            }
+        }
+        Rc::new(Expr::Map(entries))
+    }
+
+    /// Whether the value counts as true in a condition. `#f` is the only false value, so `0`, `()`
+    /// and `""` are all true.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Expr::Bool(false))
+    }
+
+    /// Prints the expression in the given style, without evaluating anything.
+    pub fn printed(&self, style: PrintStyle) -> Printed<'_> {
+        Printed { expr: self, style }
+    }
+
+    fn take_children(&mut self) -> Vec<Rc<Expr>> {
+        match self {
+            Expr::List(xs) | Expr::Vector(xs) => mem::take(xs),
+            Expr::DottedList(xs, tail) => {
+                let mut children = mem::take(xs);
+                children.push(mem::replace(tail, Rc::new(Expr::List(Vec::new()))));
+                children
+            }
+            Expr::Map(kvs) => mem::take(kvs).into_iter().flat_map(|(k, v)| [k, v]).collect(),
+            Expr::Lambda(f) => vec![mem::replace(&mut f.body, Rc::new(Expr::List(Vec::new())))],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Looks up `key` in a map, returning `None` if `self` is not a map or lacks the key.
+    pub fn map_get(&self, key: &Expr) -> Option<Rc<Expr>> {
+        match self {
+            Expr::Map(kvs) => kvs.iter().find(|(k, _)| **k == *key).map(|(_, v)| v.clone()),
+            _ => None,
+        }
     }
+}
+
+/// How strings and characters are written by `Expr::printed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintStyle {
+    /// As literals the parser reads back as the same value: `"a\nb"`, `#\space`.
+    Write,
+    /// Just their contents, for output meant for people: `a`, then a new line, then `b`.
+    Display,
+}
 
+/// An `Expr` along with the style to print it in; see `Expr::printed`.
+pub struct Printed<'e> {
+    expr: &'e Expr,
+    style: PrintStyle,
+}
+
+/// Writes a float so that it reads back as the same value, and as a float rather than an integer.
+fn write_number(f: &mut fmt::Formatter, n: f64) -> fmt::Result {
+    if n.is_nan() {
+        write!(f, "+nan.0")
+    } else if n.is_infinite() {
+        write!(f, "{}inf.0", if n > 0.0 { "+" } else { "-" })
+    } else {
+        // Debug is the shortest form that round-trips, keeps the `.0` on integral values so they
+        // read back as floats rather than integers, and switches to an exponent for very large
+        // and very small magnitudes.
+        write!(f, "{:?}", n)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            '\0' => write!(f, "\\0")?,
+            c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+fn write_char(f: &mut fmt::Formatter, c: char) -> fmt::Result {
+    match c {
+        ' ' => write!(f, "#\\space"),
+        '\n' => write!(f, "#\\newline"),
+        '\t' => write!(f, "#\\tab"),
+        '\r' => write!(f, "#\\return"),
+        '\0' => write!(f, "#\\nul"),
+        '\u{7}' => write!(f, "#\\alarm"),
+        '\u{8}' => write!(f, "#\\backspace"),
+        '\u{1b}' => write!(f, "#\\escape"),
+        '\u{7f}' => write!(f, "#\\delete"),
+        c if c.is_control() || c.is_whitespace() => write!(f, "#\\x{:x}", c as u32),
+        c => write!(f, "#\\{}", c),
+    }
+}
+
+/// A piece of output still to be written while printing.
+enum Pending<'e> {
+    Expr(&'e Expr),
+    Text(&'static str),
+}
+
+/// Written with an explicit stack rather than recursively, so that printing deeply nested data
+/// doesn't overflow the stack.
+impl fmt::Display for Printed<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut pending = vec![Pending::Expr(self.expr)];
+        while let Some(next) = pending.pop() {
+            let expr = match next {
+                Pending::Text(text) => {
+                    write!(f, "{}", text)?;
+                    continue;
+                }
+                Pending::Expr(expr) => expr,
+            };
+            let (open, close, elements, tail): (_, _, Vec<&Expr>, _) = match expr {
+                Expr::Symbol(s) => {
+                    write!(f, "{}", s)?;
+                    continue;
+                }
+                Expr::Int(n) => {
+                    write!(f, "{}", n)?;
+                    continue;
+                }
+                Expr::BigInt(n) => {
+                    write!(f, "{}", n)?;
+                    continue;
+                }
+                Expr::Rational(r) => {
+                    write!(f, "{}", r)?;
+                    continue;
+                }
+                Expr::Bool(b) => {
+                    write!(f, "{}", if *b { "#t" } else { "#f" })?;
+                    continue;
+                }
+                Expr::FNum(n) => {
+                    write_number(f, *n)?;
+                    continue;
+                }
+                Expr::Lambda(_) => {
+                    write!(f, "#<procedure>")?;
+                    continue;
+                }
+                Expr::Native(native) => {
+                    write!(f, "#<procedure {}>", native.name)?;
+                    continue;
+                }
+                Expr::Str(s) => {
+                    match self.style {
+                        PrintStyle::Write => write_string(f, s)?,
+                        PrintStyle::Display => write!(f, "{}", s)?,
+                    }
+                    continue;
+                }
+                Expr::Char(c) => {
+                    match self.style {
+                        PrintStyle::Write => write_char(f, *c)?,
+                        PrintStyle::Display => write!(f, "{}", c)?,
+                    }
+                    continue;
+                }
+                Expr::List(xs) => ("(", ")", xs.iter().map(|x| &**x).collect(), None),
+                Expr::DottedList(xs, tail) => ("(", ")", xs.iter().map(|x| &**x).collect(), Some(&**tail)),
+                Expr::Vector(xs) => ("[", "]", xs.iter().map(|x| &**x).collect(), None),
+                Expr::Map(kvs) => ("{", "}", kvs.iter().flat_map(|(k, v)| [&**k, &**v]).collect(), None),
+            };
+            write!(f, "{}", open)?;
+            pending.push(Pending::Text(close));
+            if let Some(tail) = tail {
+                pending.push(Pending::Expr(tail));
+                pending.push(Pending::Text(" . "));
+            }
+            for (i, element) in elements.into_iter().enumerate().rev() {
+                pending.push(Pending::Expr(element));
+                if i > 0 {
+                    pending.push(Pending::Text(" "));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the expression in `PrintStyle::Write`, which the parser reads back as an equal
+/// expression. Symbols are written as they are, so this only holds for symbols the lexer can
+/// produce, and functions, which have no literal syntax, are written as `#<procedure>` or, for
+/// native functions, `#<procedure name>`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.printed(PrintStyle::Write))
+    }
 }
 
 #[cfg(test)]
@@ -43,10 +439,10 @@ mod test {
     #[test]
     fn build_symbol() {
         let sym_str = "a";
-        let sym = Expr::symbol(&sym_str);
+        let sym = Expr::symbol(sym_str);
         match &*sym {
             Expr::Symbol(s) => assert_eq!(sym_str, s),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
 
@@ -56,7 +452,7 @@ mod test {
         let fnum = Expr::fnum(val);
         match &*fnum {
             Expr::FNum(n) => assert!((n - val).abs() <= 1e-8),
-            _ => assert!(false),
+            _ => panic!(),
         }
     }
 
@@ -64,6 +460,49 @@ mod test {
     fn check_fnum_equality() {
         assert_eq!(Expr::FNum(1.0), Expr::FNum(1.0));
         assert_eq!(Expr::FNum(0.99), Expr::FNum(0.99));
+        assert_eq!(Expr::FNum(f64::INFINITY), Expr::FNum(f64::INFINITY));
+        assert_ne!(Expr::FNum(f64::INFINITY), Expr::FNum(f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn ints_are_distinct_from_fnums() {
+        assert_eq!(Expr::Int(1), Expr::Int(1));
+        assert_ne!(Expr::Int(1), Expr::FNum(1.0));
+        assert_eq!("1 1.0", format!("{} {}", Expr::Int(1), Expr::FNum(1.0)));
+    }
+
+    #[test]
+    fn build_string() {
+        let s = Expr::string("hello world");
+        match &*s {
+            Expr::Str(s) => assert_eq!("hello world", s),
+            _ => panic!(),
+        }
+        assert_ne!(Expr::string("a"), Expr::symbol("a"));
+    }
+
+    #[test]
+    fn build_map_replaces_duplicate_keys() {
+        let m = Expr::map(&[
+            (Expr::symbol("a"), Expr::fnum(1.0)),
+            (Expr::symbol("b"), Expr::fnum(2.0)),
+            (Expr::symbol("a"), Expr::fnum(3.0)),
+        ]);
+        match &*m {
+            Expr::Map(kvs) => assert_eq!(2, kvs.len()),
+            _ => panic!(),
+        }
+        assert_eq!(Some(Expr::fnum(3.0)), m.map_get(&Expr::Symbol("a".into())));
+        assert_eq!(None, m.map_get(&Expr::Symbol("c".into())));
+    }
+
+    #[test]
+    fn map_equality_ignores_order() {
+        let m1 = Expr::map(&[(Expr::symbol("a"), Expr::fnum(1.0)), (Expr::symbol("b"), Expr::fnum(2.0))]);
+        let m2 = Expr::map(&[(Expr::symbol("b"), Expr::fnum(2.0)), (Expr::symbol("a"), Expr::fnum(1.0))]);
+        assert_eq!(m1, m2);
+        assert_ne!(m1, Expr::map(&[(Expr::symbol("a"), Expr::fnum(1.0))]));
+        assert_ne!(Expr::vector(&[Expr::fnum(1.0)]), Expr::list(&[Expr::fnum(1.0)]));
     }
 
     #[test]
@@ -76,7 +515,44 @@ mod test {
                 assert_eq!(Expr::fnum(1.0), xs[1]);
                 assert_eq!(Expr::fnum(1.0), xs[2]);
             },
-            _ => assert!(false),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn dotted_joins_list_tails() {
+        let a = Expr::symbol("a");
+        let b = Expr::symbol("b");
+        assert_eq!(Expr::list(&[a.clone(), b.clone()]), Expr::dotted(&[Expr::symbol("a")], Expr::list(&[Expr::symbol("b")])));
+        assert_eq!(b, Expr::dotted(&[], b.clone()));
+        let nested = Expr::dotted(&[Expr::symbol("a")], Expr::dotted(&[Expr::symbol("b")], Expr::fnum(1.0)));
+        match &*nested {
+            Expr::DottedList(xs, tail) => {
+                assert_eq!(&vec![a, b], xs);
+                assert_eq!(Expr::fnum(1.0), *tail);
+            }
+            _ => panic!(),
         }
     }
+
+    #[test]
+    fn lambdas_are_only_equal_to_themselves() {
+        let body = Expr::list(&[Expr::symbol("+"), Expr::symbol("x"), Expr::int(1)]);
+        let f = Expr::lambda(&["x".to_string()], None, body.clone(), &[]);
+        assert_eq!(f, f.clone());
+        assert_ne!(f, Expr::lambda(&["x".to_string()], None, body, &[]));
+        assert_eq!("#<procedure>", f.to_string());
+        let g = Expr::native("g", Arity::Exactly(0), |_| EvalResult::Unit);
+        assert_eq!(g, g.clone());
+        assert_ne!(g, Expr::native("g", Arity::Exactly(0), |_| EvalResult::Unit));
+        assert_eq!("#<procedure g>", g.to_string());
+    }
+
+    #[test]
+    fn arity_checks_the_argument_count() {
+        assert_eq!(Ok(()), Arity::Exactly(2).check(2));
+        assert_eq!(Err("Provided 1 arguments but expected 2".to_string()), Arity::Exactly(2).check(1));
+        assert_eq!(Ok(()), Arity::AtLeast(1).check(3));
+        assert_eq!(Err("Provided 0 arguments but expected at least 1".to_string()), Arity::AtLeast(1).check(0));
+    }
 }
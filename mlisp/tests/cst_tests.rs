@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod cst_tests {
+    use mlisp::cst::{parse_cst, CstError, Node, TriviaKind};
+    use mlisp::lex::lex;
+    use mlisp::parse::parse_program;
+    use mlisp::span::Pos;
+
+    const PROGRAM: &str = "; adds one\n(fn add-1 (x)\n  (+ x 1)) #| block #| nested |# |#\n\n#;(skipped)\n'[a  \"b\\n\" #\\c] {k 1}\n";
+
+    #[test]
+    fn prints_back_byte_for_byte() {
+        let tree = parse_cst(PROGRAM).unwrap();
+        assert_eq!(PROGRAM, tree.to_string());
+    }
+
+    #[test]
+    fn round_trips_unusual_trivia() {
+        for source in &["", "   ", "; only a comment", "x", "\t(a\r\n b)  ;end", "#|λ|#'λ ;λ\n"] {
+            assert_eq!(*source, parse_cst(source).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn keeps_trivia_with_exact_ranges() {
+        let tree = parse_cst(PROGRAM).unwrap();
+        let leading = &tree.nodes[0].first_token().leading;
+        let kinds: Vec<TriviaKind> = leading.iter().map(|t| t.kind).collect();
+        assert_eq!(vec![TriviaKind::LineComment, TriviaKind::Whitespace], kinds);
+        assert_eq!("; adds one", leading[0].text);
+        assert_eq!(0..10, leading[0].span.range());
+
+        let block = &tree.nodes[1].first_token().leading[1];
+        assert_eq!(TriviaKind::BlockComment, block.kind);
+        assert_eq!("#| block #| nested |# |#", &PROGRAM[block.span.range()]);
+        assert_eq!(Pos::new(36, 3, 12), block.span.start);
+
+        assert_eq!("\n", tree.trailing[0].text);
+    }
+
+    #[test]
+    fn nodes_know_their_source() {
+        let tree = parse_cst(PROGRAM).unwrap();
+        assert_eq!(4, tree.nodes.len());
+        assert_eq!("(fn add-1 (x)\n  (+ x 1))", tree.nodes[0].to_string());
+        assert_eq!(&PROGRAM[tree.nodes[0].span().range()], tree.nodes[0].to_string());
+        match &tree.nodes[1] {
+            Node::Prefixed { prefix, node, .. } => {
+                assert_eq!("#;", prefix.text);
+                assert_eq!("(skipped)", node.to_string());
+            }
+            other => panic!("Expected a datum comment, got {:?}", other),
+        }
+        match &tree.nodes[2] {
+            Node::Prefixed { node, .. } => match &**node {
+                Node::Seq { children, .. } => {
+                    let texts: Vec<&str> = children.iter().map(|c| c.first_token().text).collect();
+                    assert_eq!(vec!["a", "\"b\\n\"", "#\\c"], texts);
+                }
+                other => panic!("Expected a vector, got {:?}", other),
+            },
+            other => panic!("Expected a quoted form, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lowers_to_the_same_exprs_as_the_parser() {
+        let tree = parse_cst(PROGRAM).unwrap();
+        let expected = parse_program(&lex(PROGRAM).unwrap()).unwrap();
+        assert_eq!(expected, tree.lower().unwrap());
+        assert_eq!(None, tree.nodes[1].lower().unwrap());
+        assert_eq!(Some(expected[0].clone()), tree.nodes[0].lower().unwrap());
+    }
+
+    #[test]
+    fn prefixes_skip_datum_comments() {
+        for source in &["'#;a b", "`#;(a) #;b ,c", "#;#;a b c", "('#;a b)"] {
+            let tree = parse_cst(source).unwrap();
+            let expected = parse_program(&lex(source).unwrap()).unwrap();
+            assert_eq!(expected.len(), tree.nodes.iter().filter(|n| n.lower().unwrap().is_some()).count());
+            assert_eq!(expected, tree.lower().unwrap());
+            assert_eq!(*source, tree.to_string());
+        }
+        let tree = parse_cst("'#;a b").unwrap();
+        assert_eq!(1, tree.nodes.len());
+        match &tree.nodes[0] {
+            Node::Prefixed { prefix, comments, node } => {
+                assert_eq!("'", prefix.text);
+                assert_eq!(vec!["#;a"], comments.iter().map(|c| c.to_string()).collect::<Vec<_>>());
+                assert_eq!("b", node.to_string());
+            }
+            other => panic!("Expected a quoted form, got {:?}", other),
+        }
+        assert_eq!(tree.lower().unwrap()[0], tree.nodes[0].lower().unwrap().unwrap());
+    }
+
+    #[test]
+    fn lowering_reports_semantic_errors() {
+        let tree = parse_cst("{a}").unwrap();
+        assert!(tree.lower().is_err());
+    }
+
+    #[test]
+    fn structural_errors() {
+        match parse_cst("(a\n  [b)") {
+            Err(err @ CstError::Parse(_)) => assert_eq!(Pos::new(7, 2, 5), err.span().start),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        match parse_cst("(a (b c)") {
+            Err(err) => assert_eq!("1:1: Unclosed delimiter: ( opened at 1:1 is never closed", err.to_string()),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+        assert!(matches!(parse_cst("\"open"), Err(CstError::Lex(_))));
+        assert!(matches!(parse_cst("a '"), Err(CstError::Parse(_))));
+    }
+
+    #[test]
+    fn deeply_nested_trees_print() {
+        let depth = 50_000;
+        let source = format!("{} x {}", "(".repeat(depth), ")".repeat(depth));
+        let tree = parse_cst(&source).unwrap();
+        assert_eq!(source, tree.to_string());
+    }
+}
@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod eval_tests {
+    use mlisp::bigint::BigInt;
     use mlisp::eval::{eval, gen_print_output, Environment, EvalResult};
+    use mlisp::rational::Rational;
     use mlisp::types::Expr;
+    use std::rc::Rc;
 
     #[test]
     fn add_and_check_simple_var_in_env() {
@@ -142,6 +145,286 @@ mod eval_tests {
         assert_eq!(EvalResult::Expr(expected), r);
     }
 
+    fn eval_op(op: &str, args: &[Rc<Expr>]) -> EvalResult {
+        let mut form = vec![Expr::symbol(op)];
+        form.extend(args.iter().cloned());
+        eval(Expr::list(&form), &mut Environment::empty())
+    }
+
+    #[test]
+    fn integer_arithmetic_stays_exact() {
+        let big = Expr::int(99_999_999);
+        assert_eq!(EvalResult::Expr(Expr::int(9_999_999_800_000_001)), eval_op("*", &[big.clone(), big]));
+        assert_eq!(EvalResult::Expr(Expr::int(1 << 53)), eval_op("+", &[Expr::int((1 << 53) - 1), Expr::int(1)]));
+        assert_eq!(EvalResult::Expr(Expr::int(-5)), eval_op("-", &[Expr::int(5)]));
+        assert_eq!(EvalResult::Expr(Expr::int(3)), eval_op("/", &[Expr::int(12), Expr::int(4)]));
+    }
+
+    #[test]
+    fn mixed_arithmetic_promotes_to_float() {
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.5)), eval_op("+", &[Expr::int(1), Expr::fnum(2.5)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), eval_op("*", &[Expr::int(2), Expr::fnum(1.0)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(3.5)), eval_op("/", &[Expr::int(7), Expr::fnum(2.0)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(f64::INFINITY)), eval_op("/", &[Expr::fnum(1.0), Expr::int(0)]));
+    }
+
+    #[test]
+    fn integer_overflow_promotes_to_bignums() {
+        let big = |s| Expr::integer(BigInt::parse(s, 10).unwrap());
+        let cases = [
+            ("+", [Expr::int(i64::MAX), Expr::int(1)], "9223372036854775808"),
+            ("-", [Expr::int(i64::MIN), Expr::int(1)], "-9223372036854775809"),
+            ("*", [Expr::int(99_999_999_999), Expr::int(99_999_999_999)], "9999999999800000000001"),
+            ("/", [Expr::int(i64::MIN), Expr::int(-1)], "9223372036854775808"),
+        ];
+        for (op, args, expected) in &cases {
+            assert_eq!(EvalResult::Expr(big(expected)), eval_op(op, args), "{} {:?}", op, args);
+        }
+        // Results that fit back in 64 bits are plain integers again.
+        assert_eq!(EvalResult::Expr(Expr::int(i64::MAX)), eval_op("-", &[big("9223372036854775808"), Expr::int(1)]));
+        assert_eq!(EvalResult::Expr(Expr::int(3)), eval_op("quotient", &[big("27670116110564327424"), big("9223372036854775808")]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(1e19 + 0.5)), eval_op("+", &[big("10000000000000000000"), Expr::fnum(0.5)]));
+    }
+
+    #[test]
+    fn division_of_integers_is_exact() {
+        let ratio = |n, d| Expr::rational(Rational::new(BigInt::from_i64(n), BigInt::from_i64(d)).unwrap());
+        let third = eval_op("/", &[Expr::int(1), Expr::int(3)]);
+        assert_eq!(EvalResult::Expr(ratio(1, 3)), third);
+        assert_eq!(EvalResult::Expr(ratio(-7, 2)), eval_op("/", &[Expr::int(-14), Expr::int(4)]));
+        assert_eq!(EvalResult::Expr(ratio(1, 3)), eval_op("/", &[Expr::int(3)]));
+        assert_eq!(EvalResult::Expr(Expr::int(-2)), eval_op("/", &[ratio(-1, 2)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.25)), eval_op("/", &[Expr::fnum(4.0)]));
+        assert_eq!(EvalResult::Expr(Expr::int(-3)), eval_op("-", &[Expr::int(3)]));
+        assert_eq!(EvalResult::Expr(ratio(1, 6)), eval_op("-", &[ratio(1, 2), ratio(1, 3)]));
+        assert_eq!(EvalResult::Expr(Expr::int(1)), eval_op("*", &[Expr::int(3), ratio(1, 3)]));
+        assert_eq!(EvalResult::Expr(Expr::int(1)), eval_op("+", &[ratio(1, 3), ratio(2, 3)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(0.75)), eval_op("+", &[ratio(1, 4), Expr::fnum(0.5)]));
+    }
+
+    #[test]
+    fn numerator_denominator_and_exact_to_inexact() {
+        let ratio = |n, d| Expr::rational(Rational::new(BigInt::from_i64(n), BigInt::from_i64(d)).unwrap());
+        assert_eq!(EvalResult::Expr(Expr::int(-3)), eval_op("numerator", &[ratio(6, -8)]));
+        assert_eq!(EvalResult::Expr(Expr::int(4)), eval_op("denominator", &[ratio(6, -8)]));
+        assert_eq!(EvalResult::Expr(Expr::int(5)), eval_op("numerator", &[Expr::int(5)]));
+        assert_eq!(EvalResult::Expr(Expr::int(1)), eval_op("denominator", &[Expr::int(5)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(-0.75)), eval_op("exact->inexact", &[ratio(6, -8)]));
+        assert_eq!(EvalResult::Expr(Expr::fnum(5.0)), eval_op("exact->inexact", &[Expr::int(5)]));
+        for (op, arg) in [("numerator", Expr::fnum(0.5)), ("denominator", Expr::string("1/2")), ("exact->inexact", Expr::symbol("x"))] {
+            match eval_op(op, &[arg]) {
+                EvalResult::Err(_) => {}
+                r => panic!("Expected an error for {}, got {:?}", op, r),
+            }
+        }
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        for args in [[Expr::int(1), Expr::int(0)], [Expr::integer(BigInt::parse("100000000000000000000", 10).unwrap()), Expr::int(0)]] {
+            match eval_op("/", &args) {
+                EvalResult::Err(_) => {}
+                r => panic!("Expected an error for {:?}, got {:?}", args, r),
+            }
+        }
+    }
+
+    #[test]
+    fn quotient_remainder_and_modulo() {
+        let cases = [
+            ("quotient", 7, 2, 3),
+            ("quotient", -7, 2, -3),
+            ("remainder", -7, 2, -1),
+            ("remainder", 7, -2, 1),
+            ("modulo", -7, 2, 1),
+            ("modulo", 7, -2, -1),
+            ("modulo", i64::MIN, -1, 0),
+        ];
+        for (op, a, b, expected) in cases {
+            assert_eq!(EvalResult::Expr(Expr::int(expected)), eval_op(op, &[Expr::int(a), Expr::int(b)]), "{} {} {}", op, a, b);
+        }
+        assert_eq!(EvalResult::Expr(Expr::fnum(1.0)), eval_op("modulo", &[Expr::fnum(-7.0), Expr::int(2)]));
+        for args in [[Expr::int(7), Expr::int(0)], [Expr::fnum(7.5), Expr::int(2)], [Expr::int(7), Expr::string("2")]] {
+            match eval_op("quotient", &args) {
+                EvalResult::Err(_) => {}
+                r => panic!("Expected an error for {:?}, got {:?}", args, r),
+            }
+        }
+    }
+
+    #[test]
+    fn quote_returns_datum_unevaluated() {
+        let datum = Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]);
+        let expr = Expr::list(&[Expr::symbol("quote"), datum.clone()]);
+        let mut env = Environment::empty();
+        assert_eq!(EvalResult::Expr(datum), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn quoted_variables_are_not_reevaluated() {
+        let datum = Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]);
+        let mut env = Environment::empty();
+        env.push_context();
+        let r = eval(
+            Expr::list(&[
+                Expr::symbol("let"),
+                Expr::symbol("x"),
+                Expr::list(&[Expr::symbol("quote"), datum.clone()]),
+            ]),
+            &mut env,
+        );
+        assert_eq!(EvalResult::Unit, r);
+        assert_eq!(EvalResult::Expr(datum), eval(Expr::symbol("x"), &mut env));
+    }
+
+    #[test]
+    fn quasiquote_unquotes_and_splices() {
+        let form = |name: &str, e| Expr::list(&[Expr::symbol(name), e]);
+        // `(a ,(+ 1 2) ,@xs b)
+        let expr = form(
+            "quasiquote",
+            Expr::list(&[
+                Expr::symbol("a"),
+                form(
+                    "unquote",
+                    Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]),
+                ),
+                form("unquote-splicing", Expr::symbol("xs")),
+                Expr::symbol("b"),
+            ]),
+        );
+        let mut env = Environment::from_vars(&[(
+            "xs",
+            Expr::list(&[Expr::fnum(4.0), Expr::fnum(5.0)]),
+        )]);
+        let expected = Expr::list(&[
+            Expr::symbol("a"),
+            Expr::fnum(3.0),
+            Expr::fnum(4.0),
+            Expr::fnum(5.0),
+            Expr::symbol("b"),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn nested_quasiquote_keeps_inner_unquotes() {
+        let form = |name: &str, e| Expr::list(&[Expr::symbol(name), e]);
+        // `(a `(b ,(c ,x)))
+        let inner = form(
+            "quasiquote",
+            Expr::list(&[
+                Expr::symbol("b"),
+                form(
+                    "unquote",
+                    Expr::list(&[Expr::symbol("c"), form("unquote", Expr::symbol("x"))]),
+                ),
+            ]),
+        );
+        let expr = form("quasiquote", Expr::list(&[Expr::symbol("a"), inner]));
+        let mut env = Environment::from_vars(&[("x", Expr::fnum(1.0))]);
+        let expected = Expr::list(&[
+            Expr::symbol("a"),
+            form(
+                "quasiquote",
+                Expr::list(&[
+                    Expr::symbol("b"),
+                    form("unquote", Expr::list(&[Expr::symbol("c"), Expr::fnum(1.0)])),
+                ]),
+            ),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn unquote_outside_quasiquote_is_an_error() {
+        let expr = Expr::list(&[Expr::symbol("unquote"), Expr::symbol("x")]);
+        let mut env = Environment::empty();
+        match eval(expr, &mut env) {
+            EvalResult::Err(_) => {}
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn vector_literals_evaluate_their_elements() {
+        let expr = Expr::vector(&[
+            Expr::symbol("x"),
+            Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]),
+        ]);
+        let mut env = Environment::from_vars(&[("x", Expr::string("x"))]);
+        let expected = Expr::vector(&[Expr::string("x"), Expr::fnum(3.0)]);
+        assert_eq!(EvalResult::Expr(expected), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn map_literals_evaluate_keys_and_values() {
+        let expr = Expr::map(&[(
+            Expr::string("sum"),
+            Expr::list(&[Expr::symbol("+"), Expr::fnum(1.0), Expr::fnum(2.0)]),
+        )]);
+        let mut env = Environment::empty();
+        let expected = Expr::map(&[(Expr::string("sum"), Expr::fnum(3.0))]);
+        assert_eq!(EvalResult::Expr(expected), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn map_equality_ignores_order() {
+        let m1 = Expr::map(&[(Expr::string("a"), Expr::fnum(1.0)), (Expr::string("b"), Expr::fnum(2.0))]);
+        let m2 = Expr::map(&[(Expr::string("b"), Expr::fnum(2.0)), (Expr::string("a"), Expr::fnum(1.0))]);
+        let expr = Expr::list(&[Expr::symbol("="), m1, m2]);
+        let mut env = Environment::empty();
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn char_integer_conversions() {
+        assert_eq!(
+            EvalResult::Expr(Expr::int(955)),
+            eval_op("char->integer", &[Expr::character('λ')])
+        );
+        assert_eq!(
+            EvalResult::Expr(Expr::character('A')),
+            eval_op("integer->char", &[Expr::int(65)])
+        );
+        for bad in &[Expr::int(-1), Expr::fnum(65.0), Expr::int(55296), Expr::string("A")] {
+            match eval_op("integer->char", std::slice::from_ref(bad)) {
+                EvalResult::Err(_) => {}
+                r => panic!("Expected an error for {:?}, got {:?}", bad, r),
+            }
+        }
+    }
+
+    #[test]
+    fn char_case_and_classes() {
+        let t = EvalResult::Expr(Expr::boolean(true));
+        let f = EvalResult::Expr(Expr::boolean(false));
+        assert_eq!(EvalResult::Expr(Expr::character('Q')), eval_op("char-upcase", &[Expr::character('q')]));
+        assert_eq!(EvalResult::Expr(Expr::character('ß')), eval_op("char-upcase", &[Expr::character('ß')]));
+        assert_eq!(EvalResult::Expr(Expr::character('é')), eval_op("char-downcase", &[Expr::character('É')]));
+        assert_eq!(t, eval_op("char-alphabetic?", &[Expr::character('z')]));
+        assert_eq!(f, eval_op("char-alphabetic?", &[Expr::character('1')]));
+        assert_eq!(t, eval_op("char-numeric?", &[Expr::character('1')]));
+        assert_eq!(t, eval_op("char-whitespace?", &[Expr::character('\t')]));
+        assert_eq!(t, eval_op("char-upper-case?", &[Expr::character('A')]));
+        assert_eq!(f, eval_op("char-lower-case?", &[Expr::character('A')]));
+        assert_eq!(t, eval_op("char<?", &[Expr::character('a'), Expr::character('b'), Expr::character('c')]));
+        assert_eq!(f, eval_op("char=?", &[Expr::character('a'), Expr::character('A')]));
+        match eval_op("char-upcase", &[Expr::symbol("a")]) {
+            EvalResult::Err(_) => {}
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn chars_are_distinct_from_strings_and_symbols() {
+        let expr = Expr::list(&[Expr::symbol("="), Expr::character('a'), Expr::string("a")]);
+        let mut env = Environment::empty();
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval(expr, &mut env));
+        let expr = Expr::list(&[Expr::symbol("="), Expr::character('a'), Expr::character('a')]);
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval(expr, &mut env));
+    }
+
     #[test]
     fn equality_works_1() {
         let expr = Expr::list(&[
@@ -151,12 +434,26 @@ mod eval_tests {
             Expr::fnum(1.0),
             Expr::fnum(1.0),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
     }
 
+    #[test]
+    fn string_evaluates_to_itself() {
+        let expr = Expr::string("hello world");
+        let mut env = Environment::empty();
+        assert_eq!(EvalResult::Expr(expr.clone()), eval(expr, &mut env));
+    }
+
+    #[test]
+    fn string_equality_does_not_match_symbols() {
+        let expr = Expr::list(&[Expr::symbol("="), Expr::string("a"), Expr::symbol("a")]);
+        let mut env = Environment::empty();
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval(expr, &mut env));
+    }
+
     #[test]
     fn equality_works_2() {
         let expr = Expr::list(&[
@@ -166,7 +463,7 @@ mod eval_tests {
             Expr::symbol("a"),
             Expr::symbol("a"),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -185,7 +482,7 @@ mod eval_tests {
                 Expr::symbol("a"),
             ]),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -204,7 +501,7 @@ mod eval_tests {
                 Expr::symbol("a"),
             ]),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -219,7 +516,7 @@ mod eval_tests {
             Expr::fnum(1.0),
             Expr::fnum(1.0),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -234,7 +531,7 @@ mod eval_tests {
             Expr::symbol("a"),
             Expr::symbol("a"),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -253,7 +550,7 @@ mod eval_tests {
                 Expr::symbol("a"),
             ]),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -272,7 +569,7 @@ mod eval_tests {
                 Expr::symbol("a"),
             ]),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::empty();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -287,7 +584,7 @@ mod eval_tests {
             Expr::symbol("False"),
             Expr::symbol("False"),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -305,7 +602,7 @@ mod eval_tests {
             Expr::symbol("True"),
             Expr::symbol("True"),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -324,7 +621,7 @@ mod eval_tests {
             Expr::symbol("True"),
             Expr::symbol("False"),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -339,7 +636,7 @@ mod eval_tests {
             Expr::symbol("True"),
             Expr::symbol("True"),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -351,7 +648,7 @@ mod eval_tests {
             Expr::symbol("not"),
             Expr::symbol("True"),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -363,7 +660,7 @@ mod eval_tests {
             Expr::symbol("not"),
             Expr::symbol("False"),
         ]);
-        let expected = Expr::symbol("True");
+        let expected = Expr::boolean(true);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
@@ -379,23 +676,93 @@ mod eval_tests {
                 Expr::symbol("a"),
             ]),
         ]);
-        let expected = Expr::symbol("False");
+        let expected = Expr::boolean(false);
         let mut env = Environment::default();
         let r = eval(expr, &mut env);
         assert_eq!(EvalResult::Expr(expected), r);
     }
+
+    #[test]
+    fn only_false_is_falsy() {
+        let (a, b) = (Expr::symbol("a"), Expr::symbol("b"));
+        for (predicate, expected) in [
+            (Expr::boolean(false), &b),
+            (Expr::boolean(true), &a),
+            (Expr::int(0), &a),
+            (Expr::list(&[]), &a),
+            (Expr::string(""), &a),
+        ] {
+            let e = Expr::list(&[Expr::symbol("if"), predicate.clone(), a.clone(), b.clone()]);
+            assert_eq!(EvalResult::Expr(expected.clone()), eval(e, &mut Environment::empty()), "{}", predicate);
+        }
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("not", &[Expr::list(&[])]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("and", &[Expr::int(0), Expr::string("")]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("or", &[Expr::boolean(false)]));
+    }
+
+    #[test]
+    fn and_or_stop_once_the_answer_is_known() {
+        // (+) is an error, so evaluating it would fail.
+        let failing = Expr::list(&[Expr::symbol("+")]);
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("or", &[Expr::int(1), failing.clone()]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("and", &[Expr::boolean(false), failing.clone()]));
+        match eval_op("and", &[Expr::int(1), failing]) {
+            EvalResult::Err(_) => {}
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn exact_and_inexact_numbers_are_equal_by_value() {
+        let half = Expr::list(&[Expr::symbol("/"), Expr::int(1), Expr::int(2)]);
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("=", &[half.clone(), Expr::fnum(0.5)]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("=", &[Expr::int(1), Expr::fnum(1.0)]));
+        let third = Expr::list(&[Expr::symbol("/"), Expr::int(1), Expr::int(3)]);
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("=", &[third, Expr::fnum(1.0 / 3.0)]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("!=", &[half, Expr::fnum(0.25)]));
+    }
+
+    #[test]
+    fn structures_are_equal_element_by_element() {
+        let quoted = |e: Rc<Expr>| Expr::list(&[Expr::symbol("quote"), e]);
+        let one_two = quoted(Expr::list(&[Expr::int(1), Expr::int(2)]));
+        let twelve = quoted(Expr::list(&[Expr::int(12)]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("=", &[one_two, twelve]));
+        let nested = |n: i64| quoted(Expr::list(&[Expr::list(&[Expr::int(n)])]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("=", &[nested(1), nested(2)]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("=", &[nested(1), nested(1)]));
+        let mixed = |x: Rc<Expr>| quoted(Expr::list(&[
+            Expr::dotted(&[Expr::symbol("a")], x.clone()),
+            Expr::vector(std::slice::from_ref(&x)),
+            Expr::map(&[(Expr::string("k"), x)]),
+        ]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("=", &[mixed(Expr::int(3)), mixed(Expr::fnum(3.0))]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("=", &[mixed(Expr::int(3)), mixed(Expr::int(4))]));
+        let vector = quoted(Expr::vector(&[Expr::int(1)]));
+        let list = quoted(Expr::list(&[Expr::int(1)]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("=", &[vector, list]));
+    }
+
+    #[test]
+    fn booleans_are_only_equal_to_booleans() {
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("=", &[Expr::boolean(true), Expr::boolean(true)]));
+        let quoted_true = Expr::list(&[Expr::symbol("quote"), Expr::symbol("true")]);
+        assert_eq!(EvalResult::Expr(Expr::boolean(false)), eval_op("=", &[Expr::boolean(true), quoted_true]));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), eval_op("!=", &[Expr::boolean(false), Expr::list(&[])]));
+    }
+
     #[test]
     fn add_fn_to_env() {
         let x1_sym = "x1";
         let x2_sym = "x2";
-        let x1 = Expr::symbol(&x1_sym);
-        let x2 = Expr::symbol(&x2_sym);
+        let x1 = Expr::symbol(x1_sym);
+        let x2 = Expr::symbol(x2_sym);
         let params = Expr::list(&[x1.clone(), x2.clone()]);
         let fn_body = Expr::list(&[Expr::symbol("+"), x1.clone(), x2.clone()]);
         let f_name = "test-func";
         let expr = Expr::list(&[
             Expr::symbol("fn"),
-            Expr::symbol(&f_name),
+            Expr::symbol(f_name),
             params.clone(),
             fn_body.clone(),
         ]);
@@ -405,16 +772,44 @@ mod eval_tests {
         let r = eval(expr, &mut env);
         assert_eq!(r, EvalResult::Unit);
 
-        env.lookup(&f_name).map_or_else(
-            || assert!(false, "Expected function in environment but got None"),
-            |(params, body)| {
-                assert_eq!(&params[0], x1_sym);
-                assert_eq!(&params[1], x2_sym);
-                assert_eq!(body, fn_body);
+        env.lookup(f_name).map_or_else(
+            || panic!("Expected function in environment but got None"),
+            |f| match &*f {
+                Expr::Lambda(f) => {
+                    assert_eq!(&f.params[0], x1_sym);
+                    assert_eq!(&f.params[1], x2_sym);
+                    assert_eq!(f.body, fn_body);
+                }
+                _ => panic!("Expected a function, got {:?}", f),
             },
         );
     }
 
+    #[test]
+    fn lambda_makes_a_function_value() {
+        let mut env = Environment::default();
+        let x = Expr::symbol("x");
+        // (lambda (x) (* x x))
+        let square = Expr::list(&[
+            Expr::symbol("lambda"),
+            Expr::list(&[Expr::symbol("x")]),
+            Expr::list(&[Expr::symbol("*"), x.clone(), x.clone()]),
+        ]);
+        match eval(square.clone(), &mut env) {
+            EvalResult::Expr(f) => assert!(matches!(&*f, Expr::Lambda(_)), "Expected a function, got {:?}", f),
+            r => panic!("Expected a function, got {:?}", r),
+        }
+        assert_eq!(EvalResult::Expr(Expr::int(9)), eval(Expr::list(&[square.clone(), Expr::int(3)]), &mut env));
+        match eval(Expr::list(&[square]), &mut env) {
+            EvalResult::Err(msg) => assert_eq!("Provided 0 arguments but expected 1", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+        match eval(Expr::list(&[Expr::symbol("lambda"), x]), &mut env) {
+            EvalResult::Err(msg) => assert_eq!("Lambda expressions must follow the pattern (lambda (arg1 arg2 arg3 .. argn) <Expr>)", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
     #[test]
     fn test_func_evaluation() {
         let x = 2.0;
@@ -448,10 +843,10 @@ mod eval_tests {
             if let Expr::FNum(n) = *e {
                 assert_eq!(n, x + y + 1.0);
             } else {
-                assert!(false, format!("Expected FNum(6.0), got {:?}", e));
+                panic!("Expected FNum(6.0), got {:?}", e);
             }
         } else {
-            assert!(false, format!("Expected Expr::fnum(6.0), got {:?}", r2));
+            panic!("Expected Expr::fnum(6.0), got {:?}", r2);
         }
     }
 
@@ -467,9 +862,9 @@ mod eval_tests {
         let mut env = Environment::default();
         let result = eval(e, &mut env);
         if let EvalResult::Expr(expr) = result {
-            assert_eq!(Expr::list(&[Expr::symbol("x".into())]), expr);
+            assert_eq!(Expr::list(&[Expr::symbol("x")]), expr);
         } else {
-            assert!(false, "Expected expression, got {:?}", result);
+            panic!("Expected expression, got {:?}", result);
         }
     }
 
@@ -484,9 +879,9 @@ mod eval_tests {
         let mut env = Environment::default();
         let result = eval(e.clone(), &mut env);
         if let EvalResult::Expr(expr) = result {
-            assert_eq!(Expr::list(&[Expr::symbol("x".into())]), expr);
+            assert_eq!(Expr::list(&[Expr::symbol("x")]), expr);
         } else {
-            assert!(false, "Expected expression, got {:?}", result);
+            panic!("Expected expression, got {:?}", result);
         }
     }
 
@@ -502,9 +897,9 @@ mod eval_tests {
         let mut env = Environment::default();
         let result = eval(e.clone(), &mut env);
         if let EvalResult::Expr(expr) = result {
-            assert_eq!(Expr::list(&[Expr::symbol("y".into())]), expr);
+            assert_eq!(Expr::list(&[Expr::symbol("y")]), expr);
         } else {
-            assert!(false, "Expected expression, got {:?}", result);
+            panic!("Expected expression, got {:?}", result);
         }
     }
 
@@ -514,61 +909,60 @@ mod eval_tests {
         let e2 = Expr::fnum(3.2);
         let e3 = Expr::list(&[Expr::symbol("hello"), Expr::symbol("world")]);
 
-        let mut env = Environment::empty();
-
-        assert_eq!("hello", gen_print_output(e1.clone(), &mut env));
-        assert_eq!("3.2", gen_print_output(e2.clone(), &mut env));
-        assert_eq!("(hello world)", gen_print_output(e3.clone(), &mut env));
+        assert_eq!("hello", gen_print_output(std::slice::from_ref(&e1)));
+        assert_eq!("3.2", gen_print_output(std::slice::from_ref(&e2)));
+        assert_eq!("(hello world)", gen_print_output(std::slice::from_ref(&e3)));
+        assert_eq!("x", gen_print_output(&[Expr::character('x')]));
+        assert_eq!("[1 2.0]", gen_print_output(&[Expr::vector(&[Expr::int(1), Expr::fnum(2.0)])]));
+        assert_eq!("{a 1}", gen_print_output(&[Expr::map(&[(Expr::symbol("a"), Expr::int(1))])]));
+        assert_eq!("say \"hi\"", gen_print_output(&[Expr::string("say \"hi\"")]));
+        assert_eq!("hello 3.2 (hello world)", gen_print_output(&[e1.clone(), e2.clone(), e3.clone()]));
 
+        let mut env = Environment::empty();
         env.push_context();
-        env.add_fn(
-            "test-func",
-            &["x1".into(), "x2".into()],
-            Expr::symbol("body"),
-        )
-        .map_or_else(
-            |e| assert!(false, format!("got error {}", e)),
-            |_| {
-                assert_eq!(
-                    "<func-object: test-func>",
-                    gen_print_output(Expr::symbol("test-func"), &mut env)
-                )
-            },
-        );
         let _ = env
             .add_var("x", Expr::fnum(42.0))
-            .map_err(|e| assert!(false, format!("got error {}", e)));
-
-        let e4 = Expr::list(&[Expr::symbol("test-func"), Expr::symbol("x"), e3.clone()]);
-        assert_eq!(
-            "(<func-object: test-func> 42 (hello world))",
-            gen_print_output(e4.clone(), &mut env)
-        );
+            .map_err(|e| panic!("got error {}", e));
 
-        let e5 = Expr::list(&[
+        // In code: (print x 'x '(x y))
+        let e4 = Expr::list(&[
             Expr::symbol("print"),
-            e4.clone(),
-            e3.clone(),
-            e2.clone(),
-            e1.clone(),
+            Expr::symbol("x"),
+            Expr::list(&[Expr::symbol("quote"), Expr::symbol("x")]),
+            Expr::list(&[Expr::symbol("quote"), Expr::list(&[Expr::symbol("x"), Expr::symbol("y")])]),
         ]);
-        eval(e5.clone(), &mut env);
+        assert_eq!(EvalResult::Unit, eval(e4.clone(), &mut env));
 
         // In code: (print Hello world!)
-        let e6 = Expr::list(&[
+        let e5 = Expr::list(&[
             Expr::symbol("print"),
             Expr::symbol("Hello"),
             Expr::symbol("world!"),
         ]);
-        eval(e6.clone(), &mut env);
+        assert_eq!(EvalResult::Unit, eval(e5.clone(), &mut env));
+    }
+
+    #[test]
+    fn print_evaluates_its_arguments() {
+        let mut env = Environment::default();
+        // In code: (print (print 1))
+        let e = Expr::list(&[
+            Expr::symbol("print"),
+            Expr::list(&[Expr::symbol("print"), Expr::fnum(1.0)]),
+        ]);
+        match eval(e, &mut env) {
+            EvalResult::Err(msg) => assert_eq!("Cannot pass Unit as an argument to a function.", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
     }
 
 }
 
 #[cfg(test)]
 mod environment_tests {
-    use mlisp::eval::Environment;
-    use mlisp::types::Expr;
+    use mlisp::eval::{eval, Environment, EvalResult};
+    use mlisp::types::{Arity, Expr};
+    use std::cell::RefCell;
     use std::collections::HashMap;
     use std::rc::Rc;
 
@@ -579,7 +973,8 @@ mod environment_tests {
         let r = env.add_var("a", Expr::fnum(1.0));
         assert!(
             r.is_err(),
-            format!("Expected add_var to fail, but it succeeded: {:?}", r)
+            "Expected add_var to fail, but it succeeded: {:?}",
+            r
         );
     }
 
@@ -603,49 +998,31 @@ mod environment_tests {
     #[test]
     fn default_environment_is_correct() {
         let env = Environment::default();
-        env.lookup("False").map_or_else(
-            || assert!(false, "Expected Some, got None"),
-            |(ps, expr)| {
-                assert_eq!(0, ps.len());
-                assert_eq!(Expr::list(&[]), expr);
-            },
-        );
-        env.lookup("True").map_or_else(
-            || assert!(false, "Expected Some, got None"),
-            |(ps, expr)| {
-                assert_eq!(0, ps.len());
-                assert_eq!(Expr::list(&[Expr::fnum(1.0)]), expr);
-            },
-        );
+        assert_eq!(Some(Expr::boolean(false)), env.lookup("False"));
+        assert_eq!(Some(Expr::boolean(true)), env.lookup("True"));
     }
 
     #[test]
     fn lookup_works() {
-        let ctx = [("x".into(), (Vec::new(), Expr::fnum(1.0)))]
+        let ctx = [("x".into(), Expr::fnum(1.0))]
             .iter()
             .cloned()
-            .collect::<HashMap<String, (Vec<String>, Rc<Expr>)>>();
+            .collect::<HashMap<String, Rc<Expr>>>();
         let env = Environment {
-            contexts: vec![ctx],
+            contexts: vec![Rc::new(RefCell::new(ctx))],
         };
-        env.lookup("x").map_or_else(
-            || assert!(false, "Expected Some, got None"),
-            |(ps, expr)| {
-                assert_eq!(0, ps.len());
-                assert_eq!(Expr::fnum(1.0), expr);
-            },
-        );
+        assert_eq!(Some(Expr::fnum(1.0)), env.lookup("x"));
         assert!(env.lookup("y").is_none(), "Expected None, got Some");
     }
 
     #[test]
     fn contain_key_works() {
-        let ctx = [("x".into(), (Vec::new(), Expr::fnum(1.0)))]
+        let ctx = [("x".into(), Expr::fnum(1.0))]
             .iter()
             .cloned()
-            .collect::<HashMap<String, (Vec<String>, Rc<Expr>)>>();
+            .collect::<HashMap<String, Rc<Expr>>>();
         let env = Environment {
-            contexts: vec![ctx],
+            contexts: vec![Rc::new(RefCell::new(ctx))],
         };
         assert!(env.contains_key("x"), "Environment should contain x.");
         assert!(
@@ -668,26 +1045,56 @@ mod environment_tests {
 
         // Lookup the variable and validate
         env.lookup("a").map_or_else(
-            || assert!(false, "Failed to find var in environment."),
-            |(args, x)| {
-                assert_eq!(val, x);
-                assert_eq!(0usize, args.len());
-            },
+            || panic!("Failed to find var in environment."),
+            |x| assert_eq!(val, x),
         );
 
         // Pop context and check variable is gone
         env.pop_context();
-        env.lookup("a")
-            .map(|x| assert!(false, format!("Expected Err, got {:?}", x)));
+        if let Some(x) = env.lookup("a") {
+            panic!("Expected Err, got {:?}", x);
+        }
         assert_eq!(0usize, env.num_contexts());
     }
 
+    #[test]
+    fn set_var_changes_the_innermost_definition() {
+        let mut env = Environment::default();
+        let _ = env.add_var("a", Expr::int(1));
+        env.push_context();
+        let _ = env.add_var("a", Expr::int(2));
+        assert!(env.set_var("a", Expr::int(3)).is_ok());
+        assert_eq!(Some(Expr::int(3)), env.lookup("a"));
+        env.pop_context();
+        assert_eq!(Some(Expr::int(1)), env.lookup("a"));
+        assert!(env.set_var("b", Expr::int(1)).is_err());
+    }
+
+    #[test]
+    fn register_native_adds_a_callable_function() {
+        let mut env = Environment::default();
+        env.register_native("string-length", Arity::Exactly(1), |vals| match &*vals[0] {
+            Expr::Str(s) => EvalResult::Expr(Expr::int(s.chars().count() as i64)),
+            _ => EvalResult::Err("string-length can only be applied to a string.".into()),
+        });
+        let call = |args: &[Rc<Expr>], env: &mut Environment| {
+            eval(Expr::list(&[&[Expr::symbol("string-length")], args].concat()), env)
+        };
+        let hello = Expr::list(&[Expr::symbol("quote"), Expr::string("héllo")]);
+        assert_eq!(EvalResult::Expr(Expr::int(5)), call(&[hello], &mut env));
+        assert_eq!(EvalResult::Err("Provided 0 arguments but expected 1".into()), call(&[], &mut env));
+        assert_eq!(
+            EvalResult::Err("string-length can only be applied to a string.".into()),
+            call(&[Expr::int(1)], &mut env)
+        );
+    }
+
     #[test]
     fn add_fn_to_context_works() {
         let x1_sym = "x1";
         let x2_sym = "x2";
-        let x1 = Expr::symbol(&x1_sym);
-        let x2 = Expr::symbol(&x2_sym);
+        let x1 = Expr::symbol(x1_sym);
+        let x2 = Expr::symbol(x2_sym);
         let fn_body = Expr::list(&[Expr::symbol("+"), x1.clone(), x2.clone()]);
         let f_name = "test-func";
 
@@ -695,17 +1102,20 @@ mod environment_tests {
         env.push_context();
         assert_eq!(1, env.contexts.len());
         let _ = env.add_fn(
-            &f_name,
+            f_name,
             &[x1_sym.to_string(), x2_sym.to_string()],
             fn_body.clone(),
         );
 
-        let _ = env.lookup(&f_name).map_or_else(
-            || assert!(false, "Expected function in environment but got None"),
-            |(params, body)| {
-                assert_eq!(&params[0], x1_sym);
-                assert_eq!(&params[1], x2_sym);
-                assert_eq!(body, fn_body);
+        env.lookup(f_name).map_or_else(
+            || panic!("Expected function in environment but got None"),
+            |f| match &*f {
+                Expr::Lambda(f) => {
+                    assert_eq!(&f.params[0], x1_sym);
+                    assert_eq!(&f.params[1], x2_sym);
+                    assert_eq!(f.body, fn_body);
+                }
+                _ => panic!("Expected a function, got {:?}", f),
             },
         );
     }
@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod formatter_tests {
+    use mlisp::cst::parse_cst;
+    use mlisp::formatter::{format_source, FormatOptions};
+    use std::fs;
+    use std::process::Command;
+
+    fn format(source: &str, line_width: usize) -> String {
+        format_source(source, FormatOptions { line_width }).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    const MESSY: &str = "; A small program
+(fn add-1 (x) (+ x 1))   ; trailing note
+(let   x
+   3)
+
+
+#| block |#
+(fn long-function-name (first-argument second-argument) (if (= first-argument second-argument) (print \"the arguments are equal\") (print \"the arguments differ\")))
+(foo ; why
+  bar
+  ; before baz
+  baz)
+";
+
+    const FORMATTED: &str = "; A small program
+(fn add-1 (x) (+ x 1)) ; trailing note
+(let x 3)
+
+#| block |#
+(fn long-function-name (first-argument second-argument)
+  (if (= first-argument second-argument)
+      (print \"the arguments are equal\")
+      (print \"the arguments differ\")))
+(foo ; why
+     bar
+     ; before baz
+     baz)
+";
+
+    #[test]
+    fn formats_to_canonical_style() {
+        assert_eq!(FORMATTED, format(MESSY, 80));
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for width in &[20, 40, 80] {
+            let once = format(MESSY, *width);
+            assert_eq!(once, format(&once, *width));
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_meaning_and_comments() {
+        let formatted = format(MESSY, 20);
+        let before = parse_cst(MESSY).unwrap();
+        let after = parse_cst(&formatted).unwrap();
+        assert_eq!(before.lower().unwrap(), after.lower().unwrap());
+
+        let comments = |source: &str| -> Vec<String> {
+            let tree = parse_cst(source).unwrap();
+            let leading = tree.tokens().into_iter().flat_map(|t| t.leading.iter());
+            leading
+                .chain(tree.trailing.iter())
+                .filter(|t| !t.text.trim().is_empty())
+                .map(|t| t.text.to_string())
+                .collect()
+        };
+        assert_eq!(comments(MESSY), comments(&formatted));
+    }
+
+    #[test]
+    fn long_lines_are_broken_to_fit() {
+        let formatted = format("(let numbers [1 2 3 4 5 6 7 8 9 10 11 12])", 24);
+        assert_eq!("(let numbers\n  [1 2 3 4 5 6 7 8 9 10\n   11 12])\n", formatted);
+        assert!(formatted.lines().all(|line| line.chars().count() <= 24), "{}", formatted);
+    }
+
+    #[test]
+    fn quoted_lists_fill_lines() {
+        let formatted = format("(let words '(alpha beta gamma delta epsilon zeta))", 24);
+        assert_eq!("(let words\n  '(alpha beta gamma\n    delta epsilon zeta))\n", formatted);
+        let formatted = format("(print `(,(+ 1 2) one two three four) '(1 2 3 4 5 6 7 8 9 10))", 24);
+        assert_eq!(
+            "(print `(,(+ 1 2) one\n         two three four)\n       '(1 2 3 4 5 6 7 8\n         9 10))\n",
+            formatted
+        );
+        let formatted = format("(1 2 3 4 5 6 7 8 9 10 11 12)", 16);
+        assert_eq!("(1 2 3 4 5 6 7 8\n 9 10 11 12)\n", formatted);
+    }
+
+    #[test]
+    fn maps_keep_pairs_together() {
+        let formatted = format("{name \"mlisp\" tags [lisp toy] version 1}", 20);
+        assert_eq!("{name \"mlisp\"\n tags [lisp toy]\n version 1}\n", formatted);
+    }
+
+    #[test]
+    fn check_mode_exits_non_zero_for_unformatted_files() {
+        let dir = std::env::temp_dir().join(format!("mlisp-fmt-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let messy = dir.join("messy.mlisp");
+        let tidy = dir.join("tidy.mlisp");
+        fs::write(&messy, MESSY).unwrap();
+        fs::write(&tidy, FORMATTED).unwrap();
+
+        let fmt = |args: &[&std::path::Path]| {
+            Command::new(env!("CARGO_BIN_EXE_mlisp")).arg("fmt").args(args).status().unwrap()
+        };
+        let check = |path: &std::path::Path| {
+            Command::new(env!("CARGO_BIN_EXE_mlisp")).args(["fmt", "--check"]).arg(path).status().unwrap()
+        };
+        assert!(check(&tidy).success());
+        assert!(!check(&messy).success());
+        assert!(fmt(&[&messy]).success());
+        assert_eq!(FORMATTED, fs::read_to_string(&messy).unwrap());
+        assert!(check(&messy).success());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
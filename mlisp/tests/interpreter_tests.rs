@@ -1,14 +1,17 @@
 #[cfg(test)]
 mod interpreter_tests {
     use mlisp::eval::EvalResult;
-    use mlisp::interpreter::run_interpreter;
-    use mlisp::types::Expr;
+    use mlisp::interpreter::{run_interpreter, Interpreter};
+    use mlisp::types::{Arity, Expr};
+    use std::cell::Cell;
+    use std::fs;
+    use std::rc::Rc;
 
     #[test]
     fn simple_statement() {
         let program = "(+ 1 (- 3 2))";
-        let r = run_interpreter(&program);
-        assert_eq!(EvalResult::Expr(Expr::fnum(2.0)), r);
+        let r = run_interpreter(program);
+        assert_eq!(EvalResult::Expr(Expr::int(2)), r);
     }
 
     #[test]
@@ -18,8 +21,67 @@ mod interpreter_tests {
         (let y (add-1 x))
         (let z (+ x y))
         (= z (+ x y)))";
-        let r = run_interpreter(&program);
-        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::symbol("True")])), r);
+        let r = run_interpreter(program);
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::boolean(true)])), r);
+    }
+
+    #[test]
+    fn commented_program() {
+        let program = "; adds one to its argument
+        ((fn add-1 (x) (+ x 1)) #| the function
+           is defined #| nested |# above |#
+        #;(let x 100)
+        (add-1 2))";
+        let r = run_interpreter(program);
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::int(3)])), r);
+    }
+
+    #[test]
+    fn quoted_data() {
+        let program = "((let op '+)
+        (let args '(1 2))
+        `(,op ,@args 3))";
+        let r = run_interpreter(program);
+        let expected = Expr::list(&[
+            Expr::symbol("+"),
+            Expr::int(1),
+            Expr::int(2),
+            Expr::int(3),
+        ]);
+        assert_eq!(EvalResult::Expr(Expr::list(&[expected])), r);
+    }
+
+    #[test]
+    fn vectors_in_quasiquote() {
+        let program = "((let xs '[2 3])
+        `[1 ,@xs ,(+ 2 2)])";
+        let r = run_interpreter(program);
+        let expected = Expr::vector(&[
+            Expr::int(1),
+            Expr::int(2),
+            Expr::int(3),
+            Expr::int(4),
+        ]);
+        assert_eq!(EvalResult::Expr(Expr::list(&[expected])), r);
+    }
+
+    #[test]
+    fn mismatched_delimiter_error_has_location() {
+        match run_interpreter("(let x [1 2)\n") {
+            EvalResult::Err(msg) => assert_eq!(
+                "Parse error: 1:12: Mismatched delimiter: expected ] but found ) ([ opened at 1:8)",
+                msg
+            ),
+            r => panic!("Expected a parse error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn character_program() {
+        let program = r"((fn shift (c n) (integer->char (+ (char->integer c) n)))
+        (shift #\a 2))";
+        let r = run_interpreter(program);
+        assert_eq!(EvalResult::Expr(Expr::list(&[Expr::character('c')])), r);
     }
 
     #[test]
@@ -30,12 +92,265 @@ mod interpreter_tests {
         (let y (add-1 x))
         (let z (+ x y))
         (= z (+ x y))";
-        match run_interpreter(&program) {
+        match run_interpreter(program) {
             EvalResult::Err(_) => {}
-            _ => assert!(
-                false,
+            _ => panic!(
                 "Expected EvalResult::Err resulting from a bad parse."
             ),
         }
     }
+
+    #[test]
+    fn top_level_forms() {
+        let program = "(fn add-1 (x) (+ x 1))
+        (let x 3)
+        (let y (add-1 x))
+        (+ x y)";
+        let r = run_interpreter(program);
+        assert_eq!(EvalResult::Expr(Expr::int(7)), r);
+    }
+
+    #[test]
+    fn integers_grow_into_bignums_and_back() {
+        let program = "(let x 99999999999)
+        (let cube (* x x x))
+        (quotient cube (* x x))";
+        let r = run_interpreter(program);
+        assert_eq!(EvalResult::Expr(Expr::int(99_999_999_999)), r);
+    }
+
+    #[test]
+    fn recursion_ends_on_false_conditions() {
+        let program = "(fn fact (n) (if (= n 0) 1 (* n (fact (- n 1)))))
+        (= (fact 25) 15511210043330985984000000)";
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), run_interpreter(program));
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), run_interpreter("(= (and True True) True #t)"));
+    }
+
+    #[test]
+    fn rational_arithmetic_is_exact() {
+        let program = "(let third (/ 1 3))
+        (= (* 3 third) 1)";
+        assert_eq!(EvalResult::Expr(Expr::boolean(true)), run_interpreter(program));
+        match run_interpreter("(- 1/2 1/6)") {
+            EvalResult::Expr(r) => assert_eq!("1/3", r.to_string()),
+            r => panic!("Expected a fraction, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn empty_program() {
+        assert_eq!(EvalResult::Unit, run_interpreter("; nothing here\n"));
+    }
+
+    #[test]
+    fn evaluation_stops_at_first_error() {
+        match run_interpreter("(let x 1)\n(+)\n(let x 2)") {
+            EvalResult::Err(msg) => assert_eq!("Provided 0 arguments but expected at least 1", msg),
+            r => panic!("Expected an eval error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn trailing_junk_is_a_parse_error() {
+        match run_interpreter("(let x 1))") {
+            EvalResult::Err(msg) => assert_eq!("Parse error: 1:10: Unexpected ) encountered.", msg),
+            r => panic!("Expected a parse error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn all_parse_errors_are_reported() {
+        match run_interpreter("(let x 1))\n(print x]\n(+ x 1)") {
+            EvalResult::Err(msg) => assert_eq!(
+                "Parse error: 1:10: Unexpected ) encountered.\n\
+                 Parse error: 2:9: Mismatched delimiter: expected ) but found ] (( opened at 2:1)",
+                msg
+            ),
+            r => panic!("Expected parse errors, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn association_lists_and_rest_parameters() {
+        let program = "(let alist '((a . 1) (b . 2)))
+        (fn tail-of (first . rest) rest)
+        (fn pair (x) `(,x . ,(+ x 1)))
+        `(,(tail-of 1 2 3) ,(tail-of 1) ,(pair 1) ,@alist)";
+        let expected = Expr::list(&[
+            Expr::list(&[Expr::int(2), Expr::int(3)]),
+            Expr::list(&[]),
+            Expr::dotted(&[Expr::int(1)], Expr::int(2)),
+            Expr::dotted(&[Expr::symbol("a")], Expr::int(1)),
+            Expr::dotted(&[Expr::symbol("b")], Expr::int(2)),
+        ]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn functions_are_values() {
+        let program = "(fn twice (f x) (f (f x)))
+        (fn add-1 (x) (+ x 1))
+        (fn make-doubler () (lambda (x) (* x 2)))
+        (let fs `(,add-1 ,(make-doubler)))
+        `(,(twice add-1 1) ,((make-doubler) 5) ,((lambda (x . xs) xs) 1 2 3) ,(twice (lambda (x) (* x x)) 3) ,@fs)";
+        match run_interpreter(program) {
+            EvalResult::Expr(r) => assert_eq!("(3 10 (2 3) 81 #<procedure> #<procedure>)", r.to_string()),
+            r => panic!("Expected a list, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn closures_capture_their_defining_scope() {
+        let program = "(fn make-adder (n) (lambda (x) (+ x n)))
+        (fn make-counter (count) (lambda () (begin (set! count (+ count 1)) count)))
+        (let add-2 (make-adder 2))
+        (let n 100)
+        (let c1 (make-counter 0))
+        (let c2 (make-counter 10))
+        (c1)
+        (c2)
+        `(,(add-2 1) ,(c1) ,(c1) ,(c2))";
+        let expected = Expr::list(&[Expr::int(3), Expr::int(2), Expr::int(3), Expr::int(12)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter(program));
+    }
+
+    #[test]
+    fn functions_cannot_see_their_callers_variables() {
+        let program = "(fn get-local () local)
+        (fn caller (local) (get-local))
+        (caller 1)";
+        assert_eq!(EvalResult::Expr(Expr::symbol("local")), run_interpreter(program));
+        match run_interpreter("(fn f () (set! y 1))\n(f)") {
+            EvalResult::Err(msg) => assert_eq!("Cannot set! y, which is not defined.", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn builtins_are_function_values() {
+        let program = "(fn combine (f a b) (f a b))
+        (let plus +)
+        `(,(combine * 3 4) ,(plus 1 2) ,(= plus +) ,+)";
+        match run_interpreter(program) {
+            EvalResult::Expr(r) => assert_eq!("(12 3 #t #<procedure +>)", r.to_string()),
+            r => panic!("Expected a list, got {:?}", r),
+        }
+        let shadowed = "(fn add-all (+) (+ 1))\n(add-all -)";
+        assert_eq!(EvalResult::Expr(Expr::int(-1)), run_interpreter(shadowed));
+    }
+
+    #[test]
+    fn a_symbol_parameter_list_takes_every_argument() {
+        let expected = Expr::list(&[Expr::int(1), Expr::int(2), Expr::int(3)]);
+        assert_eq!(EvalResult::Expr(expected), run_interpreter("((lambda args args) 1 2 3)"));
+        assert_eq!(EvalResult::Expr(Expr::list(&[])), run_interpreter("(fn none args args) (none)"));
+    }
+
+    #[test]
+    fn rest_parameters_need_the_required_arguments() {
+        match run_interpreter("(fn f (a b . rest) a)\n(f 1)") {
+            EvalResult::Err(msg) => assert_eq!("Provided 1 arguments but expected at least 2", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+        match run_interpreter("(a . b)") {
+            EvalResult::Err(msg) => assert_eq!("Cannot evaluate the improper list (a . b)", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn interpreter_keeps_definitions_between_calls() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(EvalResult::Unit, interpreter.eval_str("(fn add (a b) (+ a b))\n(let total 0)"));
+        interpreter.set_global("total", Expr::int(40));
+        assert_eq!(EvalResult::Expr(Expr::int(42)), interpreter.eval_str("(add total 2)"));
+        assert_eq!(
+            EvalResult::Expr(Expr::int(3)),
+            interpreter.call("add", &[Expr::int(1), Expr::int(2)])
+        );
+        assert_eq!(Some(Expr::int(40)), interpreter.get_global("total"));
+        assert_eq!(None, interpreter.get_global("missing"));
+        assert_eq!(
+            EvalResult::Err("missing is not defined.".into()),
+            interpreter.call("missing", &[])
+        );
+        assert_eq!(
+            EvalResult::Err("total is not a function.".into()),
+            interpreter.call("total", &[])
+        );
+    }
+
+    #[test]
+    fn interpreter_calls_pass_arguments_unevaluated() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_native("double", Arity::Exactly(1), |vals| match &*vals[0] {
+            Expr::Int(n) => EvalResult::Expr(Expr::int(n * 2)),
+            _ => EvalResult::Err("double can only be applied to an integer.".into()),
+        });
+        interpreter.eval_str("(fn identity (x) x)\n(let x 1)");
+        let x = Expr::symbol("x");
+        assert_eq!(EvalResult::Expr(x.clone()), interpreter.call("identity", &[x]));
+        assert_eq!(EvalResult::Expr(Expr::int(8)), interpreter.eval_str("(double (double 2))"));
+    }
+
+    #[test]
+    fn interpreter_loads_files() {
+        let path = std::env::temp_dir().join(format!("mlisp-load-file-{}.lisp", std::process::id()));
+        fs::write(&path, "(fn square (x) (* x x))\n(let nine (square 3))").unwrap();
+        let mut interpreter = Interpreter::new();
+        let loaded = interpreter.load_file(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(EvalResult::Unit, loaded);
+        assert_eq!(Some(Expr::int(9)), interpreter.get_global("nine"));
+        assert_eq!(EvalResult::Expr(Expr::int(16)), interpreter.call("square", &[Expr::int(4)]));
+        match interpreter.load_file(&path) {
+            EvalResult::Err(msg) => assert!(msg.starts_with("Cannot read "), "Unexpected error {}", msg),
+            r => panic!("Expected an error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn dropping_an_interpreter_frees_its_functions() {
+        struct SetOnDrop(Rc<Cell<bool>>);
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.set(true);
+            }
+        }
+
+        let dropped = Rc::new(Cell::new(false));
+        let mut interpreter = Interpreter::new();
+        let guard = SetOnDrop(dropped.clone());
+        interpreter.register_native("guarded", Arity::Exactly(0), move |_| {
+            let _ = &guard;
+            EvalResult::Unit
+        });
+        let program = "(fn f () 1)
+        (fn make-adder (n) (begin (fn add (x) (if (= x 0) n (+ 1 (add (- x 1))))) add))
+        (let adders `(,(make-adder 1) [,(make-adder 2)]))";
+        assert_eq!(EvalResult::Unit, interpreter.eval_str(program));
+        let adder = Rc::downgrade(&interpreter.get_global("adders").unwrap());
+        drop(interpreter);
+        assert!(dropped.get(), "The global context was not dropped.");
+        assert!(adder.upgrade().is_none(), "The closures were not dropped.");
+    }
+
+    #[test]
+    fn closures_still_work_after_their_interpreter_is_dropped() {
+        let square = match run_interpreter("(fn sq (x) (* x x)) (fn make () (lambda (x) (sq x))) (make)") {
+            EvalResult::Expr(f) => f,
+            r => panic!("Expected a closure, got {:?}", r),
+        };
+        let mut interpreter = Interpreter::new();
+        assert_eq!(EvalResult::Unit, interpreter.eval_str("(fn add (x y) (+ x y))"));
+        let add = interpreter.get_global("add").unwrap();
+        drop(interpreter);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("square", square);
+        interpreter.set_global("add", add);
+        assert_eq!(EvalResult::Expr(Expr::int(9)), interpreter.call("square", &[Expr::int(3)]));
+        assert_eq!(EvalResult::Expr(Expr::int(5)), interpreter.call("add", &[Expr::int(2), Expr::int(3)]));
+    }
 }
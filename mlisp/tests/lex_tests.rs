@@ -1,14 +1,45 @@
 #[cfg(test)]
 mod lex_tests {
-    use mlisp::lex::{lex, Token};
+    use mlisp::lex::{lex, lex_reader, LexError, Lexer, Token, TokenKind};
+    use mlisp::span::{Pos, Span};
+    use std::borrow::Cow;
+    use std::io::{self, Read};
+
+    /// Hands out its input one byte per `read` call, so characters get split across reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                None => Ok(0),
+                Some((b, rest)) => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+            }
+        }
+    }
+
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("pipe closed"))
+        }
+    }
+
+    fn kinds<'a>(tokens: &[Token<'a>]) -> Vec<TokenKind<'a>> {
+        tokens.iter().map(|t| t.kind.clone()).collect()
+    }
 
     #[test]
     fn can_lex_lpar() {
         lex("(").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| {
                 assert_eq!(1, tokens.len());
-                assert_eq!(Token::LPar, tokens[0]);
+                assert_eq!(TokenKind::LPar, tokens[0].kind);
             },
         );
     }
@@ -16,10 +47,10 @@ mod lex_tests {
     #[test]
     fn can_lex_rpar() {
         lex(")").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| {
                 assert_eq!(1, tokens.len());
-                assert_eq!(Token::RPar, tokens[0]);
+                assert_eq!(TokenKind::RPar, tokens[0].kind);
             },
         );
     }
@@ -27,10 +58,10 @@ mod lex_tests {
     #[test]
     fn can_lex_literal() {
         lex("hello-world").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| {
                 assert_eq!(1, tokens.len());
-                assert_eq!(Token::Literal("hello-world".into()), tokens[0]);
+                assert_eq!(TokenKind::Literal("hello-world".into()), tokens[0].kind);
             },
         );
     }
@@ -39,16 +70,16 @@ mod lex_tests {
     fn lex_test_1() {
         let input = "(+ 10 -2)";
         let output = vec![
-            Token::LPar,
-            Token::Literal("+".into()),
-            Token::Literal("10".into()),
-            Token::Literal("-2".into()),
-            Token::RPar,
+            TokenKind::LPar,
+            TokenKind::Literal("+".into()),
+            TokenKind::Literal("10".into()),
+            TokenKind::Literal("-2".into()),
+            TokenKind::RPar,
         ];
 
         match lex(input) {
-            Ok(actual) => assert_eq!(output, actual),
-            _ => assert!(false),
+            Ok(actual) => assert_eq!(output, kinds(&actual)),
+            Err(err) => panic!("{:?}", err),
         }
     }
 
@@ -56,29 +87,384 @@ mod lex_tests {
     fn lex_test_2() {
         let input = "(let x (some-fn 1 2 3))";
         let output = vec![
-            Token::LPar,
-            Token::Literal("let".into()),
-            Token::Literal("x".into()),
-            Token::LPar,
-            Token::Literal("some-fn".into()),
-            Token::Literal("1".into()),
-            Token::Literal("2".into()),
-            Token::Literal("3".into()),
-            Token::RPar,
-            Token::RPar,
+            TokenKind::LPar,
+            TokenKind::Literal("let".into()),
+            TokenKind::Literal("x".into()),
+            TokenKind::LPar,
+            TokenKind::Literal("some-fn".into()),
+            TokenKind::Literal("1".into()),
+            TokenKind::Literal("2".into()),
+            TokenKind::Literal("3".into()),
+            TokenKind::RPar,
+            TokenKind::RPar,
         ];
 
         match lex(input) {
-            Ok(actual) => assert_eq!(output, actual),
-            _ => assert!(false),
+            Ok(actual) => assert_eq!(output, kinds(&actual)),
+            Err(err) => panic!("{:?}", err),
         }
     }
 
     #[test]
     fn lex_empty_string() {
         lex("").map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |tokens| assert_eq!(0, tokens.len()),
         );
     }
+
+    #[test]
+    fn tokens_carry_spans() {
+        let tokens = lex("(add-1\n  x)").unwrap();
+        let spans: Vec<Span> = tokens.iter().map(|t| t.span).collect();
+        assert_eq!(
+            vec![
+                Span::new(Pos::new(0, 1, 1), Pos::new(1, 1, 2)),
+                Span::new(Pos::new(1, 1, 2), Pos::new(6, 1, 7)),
+                Span::new(Pos::new(9, 2, 3), Pos::new(10, 2, 4)),
+                Span::new(Pos::new(10, 2, 4), Pos::new(11, 2, 5)),
+            ],
+            spans
+        );
+    }
+
+    #[test]
+    fn token_offsets_slice_the_input() {
+        let input = "(print héllo wörld)";
+        let tokens = lex(input).unwrap();
+        let texts: Vec<&str> = tokens
+            .iter()
+            .map(|t| &input[t.span.start.offset..t.span.end.offset])
+            .collect();
+        assert_eq!(vec!["(", "print", "héllo", "wörld", ")"], texts);
+    }
+
+    #[test]
+    fn lex_string_with_spaces() {
+        let tokens = lex("(print \"hello world\")").unwrap();
+        assert_eq!(
+            vec![
+                TokenKind::LPar,
+                TokenKind::Literal("print".into()),
+                TokenKind::Str("hello world".into()),
+                TokenKind::RPar,
+            ],
+            kinds(&tokens)
+        );
+        assert_eq!(Span::new(Pos::new(7, 1, 8), Pos::new(20, 1, 21)), tokens[2].span);
+    }
+
+    #[test]
+    fn lex_string_escapes() {
+        let tokens = lex(r#""a\nb \"q\" \\ \u{41}\u{1F600}""#).unwrap();
+        assert_eq!(vec![TokenKind::Str("a\nb \"q\" \\ A\u{1F600}".into())], kinds(&tokens));
+    }
+
+    #[test]
+    fn string_ends_literal() {
+        let tokens = lex(r#"abc"d""#).unwrap();
+        assert_eq!(
+            vec![TokenKind::Literal("abc".into()), TokenKind::Str("d".into())],
+            kinds(&tokens)
+        );
+    }
+
+    #[test]
+    fn unterminated_string_is_an_error() {
+        match lex("(print \"oops)") {
+            Err(LexError::UnterminatedString(span)) => assert_eq!(Pos::new(7, 1, 8), span.start),
+            other => panic!("Expected UnterminatedString, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_escapes_are_errors() {
+        for input in &[r#""\q""#, r#""\u41""#, r#""\u{110000}""#, r#""\u{zz}""#] {
+            match lex(input) {
+                Err(LexError::InvalidEscape(_, span)) => assert_eq!(Pos::new(1, 1, 2), span.start),
+                other => panic!("Expected InvalidEscape for {}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn line_comments_are_skipped() {
+        let tokens = lex("; leading note\n(a ; trailing note\n b);eof").unwrap();
+        assert_eq!(
+            vec![
+                TokenKind::LPar,
+                TokenKind::Literal("a".into()),
+                TokenKind::Literal("b".into()),
+                TokenKind::RPar,
+            ],
+            kinds(&tokens)
+        );
+        assert_eq!(Pos::new(35, 3, 2), tokens[2].span.start);
+    }
+
+    #[test]
+    fn semicolon_ends_literal() {
+        let tokens = lex("abc;def").unwrap();
+        assert_eq!(vec![TokenKind::Literal("abc".into())], kinds(&tokens));
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let tokens = lex("a #| one #| two |# still one |# b").unwrap();
+        assert_eq!(
+            vec![TokenKind::Literal("a".into()), TokenKind::Literal("b".into())],
+            kinds(&tokens)
+        );
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_an_error() {
+        match lex("a\n #| #| |#") {
+            Err(LexError::UnterminatedComment(span)) => assert_eq!(Pos::new(3, 2, 2), span.start),
+            other => panic!("Expected UnterminatedComment, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lex_datum_comment() {
+        let tokens = lex("#;(a) b").unwrap();
+        assert_eq!(
+            vec![
+                TokenKind::DatumComment,
+                TokenKind::LPar,
+                TokenKind::Literal("a".into()),
+                TokenKind::RPar,
+                TokenKind::Literal("b".into()),
+            ],
+            kinds(&tokens)
+        );
+    }
+
+    #[test]
+    fn lex_quote_shorthands() {
+        let tokens = lex("'a `(b ,c ,@d)").unwrap();
+        assert_eq!(
+            vec![
+                TokenKind::Quote,
+                TokenKind::Literal("a".into()),
+                TokenKind::Quasiquote,
+                TokenKind::LPar,
+                TokenKind::Literal("b".into()),
+                TokenKind::Unquote,
+                TokenKind::Literal("c".into()),
+                TokenKind::UnquoteSplicing,
+                TokenKind::Literal("d".into()),
+                TokenKind::RPar,
+            ],
+            kinds(&tokens)
+        );
+    }
+
+    #[test]
+    fn numbers_and_number_like_symbols() {
+        let tokens = lex("-2 +.5 1e3 - + ... -> .foo").unwrap();
+        assert_eq!(8, tokens.len());
+    }
+
+    #[test]
+    fn malformed_numbers_are_errors() {
+        for input in &["12abc", "1.2.3", "-3x", ".5.", "(+ 1 2x)"] {
+            match lex(input) {
+                Err(LexError::InvalidNumber(..)) => {}
+                other => panic!("Expected InvalidNumber for {}, got {:?}", input, other),
+            }
+        }
+        match lex("(a\n  4b)") {
+            Err(LexError::InvalidNumber(lit, span)) => {
+                assert_eq!("4b", lit);
+                assert_eq!(Span::new(Pos::new(5, 2, 3), Pos::new(7, 2, 5)), span);
+            }
+            other => panic!("Expected InvalidNumber, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn control_characters_are_errors() {
+        match lex("(a \u{7}b)") {
+            Err(LexError::InvalidCharacter(c, span)) => {
+                assert_eq!('\u{7}', c);
+                assert_eq!(Pos::new(3, 1, 4), span.start);
+            }
+            other => panic!("Expected InvalidCharacter, got {:?}", other),
+        }
+        match lex("ab\u{0}") {
+            Err(LexError::InvalidCharacter('\u{0}', span)) => assert_eq!(Pos::new(2, 1, 3), span.start),
+            other => panic!("Expected InvalidCharacter, got {:?}", other),
+        }
+        match lex("\"bell\u{7}\"") {
+            Err(LexError::InvalidCharacter('\u{7}', _)) => {}
+            other => panic!("Expected InvalidCharacter, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unicode_whitespace_is_an_error() {
+        for input in &["a\u{a0}b", "\u{2003}"] {
+            match lex(input) {
+                Err(LexError::InvalidCharacter(..)) => {}
+                other => panic!("Expected InvalidCharacter for {:?}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn control_characters_are_allowed_in_comments() {
+        assert_eq!(1, lex("; \u{7}\n#| \u{0} |# a").unwrap().len());
+    }
+
+    #[test]
+    fn unknown_hash_syntax_is_an_error() {
+        match lex("(a #foo)") {
+            Err(LexError::UnknownToken(lit, span)) => {
+                assert_eq!("#foo", lit);
+                assert_eq!(Span::new(Pos::new(3, 1, 4), Pos::new(7, 1, 8)), span);
+            }
+            other => panic!("Expected UnknownToken, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lex_errors_display_their_location() {
+        let err = lex("(print\n  \"unterminated)").unwrap_err();
+        assert_eq!("2:3: unterminated string literal", err.to_string());
+    }
+
+    #[test]
+    fn lexer_yields_tokens_lazily() {
+        let mut lexer = Lexer::new("(a \"unterminated");
+        assert_eq!(TokenKind::LPar, lexer.next().unwrap().unwrap().kind);
+        assert_eq!(TokenKind::Literal("a".into()), lexer.next().unwrap().unwrap().kind);
+        assert!(matches!(lexer.next(), Some(Err(LexError::UnterminatedString(_)))));
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn lexer_borrows_from_input() {
+        let tokens = lex("sym \"plain\" \"esc\\n\"").unwrap();
+        assert!(matches!(&tokens[0].kind, TokenKind::Literal(Cow::Borrowed("sym"))));
+        assert!(matches!(&tokens[1].kind, TokenKind::Str(Cow::Borrowed("plain"))));
+        assert!(matches!(&tokens[2].kind, TokenKind::Str(Cow::Owned(s)) if s == "esc\n"));
+    }
+
+    #[test]
+    fn reader_matches_str_lexing() {
+        let input = "; λ comment\n(print \"héllo\\u{1F600} wörld\" 'x #| ü |# -2.5)";
+        let expected = lex(input).unwrap();
+        let actual = lex_reader(Trickle(input.as_bytes())).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn reader_reports_invalid_utf8() {
+        match lex_reader(Trickle(b"(a \xff)")) {
+            Err(LexError::Io(err, _)) => assert_eq!(io::ErrorKind::InvalidData, err.kind()),
+            other => panic!("Expected an Io error, got {:?}", other),
+        }
+        match lex_reader(Trickle(b"(a \xc3")) {
+            Err(LexError::Io(err, _)) => assert_eq!(io::ErrorKind::UnexpectedEof, err.kind()),
+            other => panic!("Expected an Io error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reader_reports_io_errors() {
+        match lex_reader(Broken) {
+            Err(LexError::Io(err, span)) => {
+                assert_eq!("pipe closed", err.to_string());
+                assert_eq!(Span::point(Pos::start()), span);
+            }
+            other => panic!("Expected an Io error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn into_owned_detaches_from_input() {
+        let token = {
+            let input = String::from("borrowed");
+            lex(&input).unwrap().remove(0).into_owned()
+        };
+        assert_eq!(TokenKind::Literal("borrowed".into()), token.kind);
+    }
+
+    #[test]
+    fn lex_brackets_and_braces() {
+        let tokens = lex("[a]{b c}x[").unwrap();
+        assert_eq!(
+            vec![
+                TokenKind::LBracket,
+                TokenKind::Literal("a".into()),
+                TokenKind::RBracket,
+                TokenKind::LBrace,
+                TokenKind::Literal("b".into()),
+                TokenKind::Literal("c".into()),
+                TokenKind::RBrace,
+                TokenKind::Literal("x".into()),
+                TokenKind::LBracket,
+            ],
+            kinds(&tokens)
+        );
+    }
+
+    #[test]
+    fn radix_literals_and_bad_hash_numbers() {
+        let tokens = lex("#x1F #b-101").unwrap();
+        assert_eq!(
+            vec![TokenKind::Literal("#x1F".into()), TokenKind::Literal("#b-101".into())],
+            kinds(&tokens)
+        );
+        match lex("(#b102)") {
+            Err(LexError::InvalidNumber(lit, span)) => {
+                assert_eq!("#b102", lit);
+                assert_eq!(Pos::new(1, 1, 2), span.start);
+            }
+            other => panic!("Expected InvalidNumber, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lex_character_literals() {
+        let tokens = lex(r"(#\a #\space #\newline #\x41 #\x #\( #\) #\λ)").unwrap();
+        assert_eq!(
+            vec![
+                TokenKind::LPar,
+                TokenKind::Char('a'),
+                TokenKind::Char(' '),
+                TokenKind::Char('\n'),
+                TokenKind::Char('A'),
+                TokenKind::Char('x'),
+                TokenKind::Char('('),
+                TokenKind::Char(')'),
+                TokenKind::Char('λ'),
+                TokenKind::RPar,
+            ],
+            kinds(&tokens)
+        );
+        assert_eq!(Span::new(Pos::new(5, 1, 6), Pos::new(12, 1, 13)), tokens[2].span);
+    }
+
+    #[test]
+    fn lex_boolean_literals() {
+        let tokens = lex("#t #true #f #false").unwrap();
+        assert_eq!(
+            vec![TokenKind::Bool(true), TokenKind::Bool(true), TokenKind::Bool(false), TokenKind::Bool(false)],
+            kinds(&tokens)
+        );
+        assert_eq!(Span::new(Pos::new(3, 1, 4), Pos::new(8, 1, 9)), tokens[1].span);
+        assert!(matches!(lex("#tru"), Err(LexError::UnknownToken(..))));
+    }
+
+    #[test]
+    fn unknown_character_names_are_errors() {
+        for input in &[r"#\bogus", r"#\xZZ", r"#\x110000", "#\\"] {
+            match lex(input) {
+                Err(LexError::UnknownToken(_, span)) => assert_eq!(Pos::start(), span.start),
+                other => panic!("Expected UnknownToken for {}, got {:?}", input, other),
+            }
+        }
+    }
 }
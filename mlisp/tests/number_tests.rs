@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod number_tests {
+    use mlisp::bigint::BigInt;
+    use mlisp::number::{parse_number, Number};
+    use mlisp::rational::Rational;
+
+    fn num(lit: &str) -> Number {
+        match parse_number(lit) {
+            Some(Ok(n)) => n,
+            other => panic!("Expected {} to be a number, got {:?}", lit, other),
+        }
+    }
+
+    fn invalid(lit: &str) {
+        match parse_number(lit) {
+            Some(Err(_)) => {}
+            other => panic!("Expected {} to be an invalid number, got {:?}", lit, other),
+        }
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(Number::Int(42), num("42"));
+        assert_eq!(Number::Int(-7), num("-7"));
+        assert_eq!(Number::Int(7), num("+7"));
+        assert_eq!(Number::Int(1_000_000), num("1_000_000"));
+    }
+
+    #[test]
+    fn radix_integers() {
+        assert_eq!(Number::Int(31), num("#x1F"));
+        assert_eq!(Number::Int(31), num("#X1f"));
+        assert_eq!(Number::Int(15), num("#o17"));
+        assert_eq!(Number::Int(10), num("#b1010"));
+        assert_eq!(Number::Int(-10), num("#b-1010"));
+        assert_eq!(Number::Int(0xffff), num("#xff_ff"));
+        assert_eq!(Number::Int(99), num("#d99"));
+    }
+
+    #[test]
+    fn decimals_and_exponents() {
+        assert_eq!(Number::Float(1.5), num("1.5"));
+        assert_eq!(Number::Float(0.5), num(".5"));
+        assert_eq!(Number::Float(-0.5), num("-.5"));
+        assert_eq!(Number::Float(1.0), num("1."));
+        assert_eq!(Number::Float(6.02e23), num("6.02e23"));
+        assert_eq!(Number::Float(1e-9), num("1E-9"));
+        assert_eq!(Number::Float(1000.0), num("1e+3"));
+        assert_eq!(Number::Float(1234.5), num("1_234.5"));
+    }
+
+    #[test]
+    fn rationals() {
+        let ratio = |n, d| Number::Rational(Rational::new(BigInt::from_i64(n), BigInt::from_i64(d)).unwrap());
+        assert_eq!(ratio(1, 2), num("1/2"));
+        assert_eq!(ratio(-3, 4), num("-3/4"));
+        assert_eq!(ratio(1, 10), num("#x1/A"));
+        assert_eq!(ratio(1, 2), num("2/4"));
+        assert_eq!(Number::Int(2), num("4/2"));
+        assert_eq!(0.5, num("2/4").to_f64());
+    }
+
+    #[test]
+    fn special_floats() {
+        assert_eq!(Number::Float(f64::INFINITY), num("+inf.0"));
+        assert_eq!(Number::Float(f64::NEG_INFINITY), num("-inf.0"));
+        assert!(num("+nan.0").to_f64().is_nan());
+    }
+
+    #[test]
+    fn inf_and_nan_words_are_symbols() {
+        for lit in &["inf", "-inf", "NaN", "nan", "infinity", "+inf", "-", "+", "...", "_1"] {
+            assert!(parse_number(lit).is_none(), "Expected {} to be a symbol", lit);
+        }
+    }
+
+    #[test]
+    fn huge_integers_become_bignums() {
+        for lit in ["1000000000000000000000000", "-9223372036854775809"] {
+            assert_eq!(Number::Big(BigInt::parse(lit, 10).unwrap()), num(lit));
+        }
+        assert_eq!(Number::Int(i64::MIN), num("-9223372036854775808"));
+        assert_eq!(Number::Big(BigInt::parse("10000000000000000", 16).unwrap()), num("#x1_0000_0000_0000_0000"));
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        for lit in &[
+            "12abc", "1.2.3", "1e", "1e+", "1_", "1__0", "1._5", "0x1F", "#x", "#xG", "#b102",
+            "#x1.5", "1/0", "1/-2", "1.5/2", "1/", "#o8",
+        ] {
+            invalid(lit);
+        }
+    }
+}
@@ -1,38 +1,73 @@
 #[cfg(test)]
 mod parse_tests {
-    use mlisp::lex::Token;
-    use mlisp::parse::parse;
-    use mlisp::types::Expr;
+    use mlisp::bigint::BigInt;
+    use mlisp::lex::{lex, lex_reader, LexError, Token, TokenKind};
+    use mlisp::parse::{
+        check_input, parse, parse_program, parse_program_recovering, parse_with, InputStatus, ParseError,
+        ParseOptions,
+    };
+    use mlisp::rational::Rational;
+    use mlisp::span::{Pos, Span};
+    use mlisp::types::{Expr, PrintStyle};
+    use std::rc::Rc;
+
+    fn tok(kind: TokenKind) -> Token {
+        Token::new(kind, Span::default())
+    }
+
+    #[test]
+    fn parse_int() {
+        parse(&[tok(TokenKind::Literal("1".into()))]).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(Expr::int(1), expr),
+        );
+    }
 
     #[test]
     fn parse_fnum() {
-        parse(&[Token::Literal("1".into())]).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+        parse(&[tok(TokenKind::Literal("1.0".into()))]).map_or_else(
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(Expr::fnum(1.0), expr),
         );
     }
 
     #[test]
     fn parse_symbol() {
-        parse(&[Token::Literal("hello".into())]).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+        parse(&[tok(TokenKind::Literal("hello".into()))]).map_or_else(
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(Expr::symbol("hello"), expr),
         )
     }
 
+    #[test]
+    fn parse_string() {
+        parse(&[tok(TokenKind::Str("1 2".into()))]).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(Expr::string("1 2"), expr),
+        )
+    }
+
+    #[test]
+    fn parse_char() {
+        parse(&lex(r"#\space").unwrap()).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(Expr::character(' '), expr),
+        )
+    }
+
     #[test]
     fn parse_list() {
         let tokens = [
-            Token::LPar,
-            Token::Literal("+".into()),
-            Token::Literal("1.8".into()),
-            Token::Literal("1.2".into()),
-            Token::RPar,
+            tok(TokenKind::LPar),
+            tok(TokenKind::Literal("+".into())),
+            tok(TokenKind::Literal("1.8".into())),
+            tok(TokenKind::Literal("1.2".into())),
+            tok(TokenKind::RPar),
         ];
         let expected = Expr::list(&[Expr::symbol("+"), Expr::fnum(1.8), Expr::fnum(1.2)]);
 
         parse(&tokens).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(expected, expr),
         );
     }
@@ -40,17 +75,17 @@ mod parse_tests {
     #[test]
     fn parse_nested_symbol() {
         let tokens = [
-            Token::LPar,
-            Token::LPar,
-            Token::Literal("A".into()),
-            Token::RPar,
-            Token::RPar,
+            tok(TokenKind::LPar),
+            tok(TokenKind::LPar),
+            tok(TokenKind::Literal("A".into())),
+            tok(TokenKind::RPar),
+            tok(TokenKind::RPar),
         ];
 
         let expected = Expr::list(&[Expr::list(&[Expr::symbol("A")])]);
 
         parse(&tokens).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(expected, expr),
         );
     }
@@ -58,19 +93,19 @@ mod parse_tests {
     #[test]
     fn nested_lists() {
         let tokens = [
-            Token::LPar,
-            Token::Literal("+".into()),
-            Token::LPar,
-            Token::Literal("+".into()),
-            Token::Literal("1.2".into()),
-            Token::Literal("-12.8".into()),
-            Token::RPar,
-            Token::LPar,
-            Token::Literal("*".into()),
-            Token::Literal("1.2".into()),
-            Token::Literal("-12.8".into()),
-            Token::RPar,
-            Token::RPar,
+            tok(TokenKind::LPar),
+            tok(TokenKind::Literal("+".into())),
+            tok(TokenKind::LPar),
+            tok(TokenKind::Literal("+".into())),
+            tok(TokenKind::Literal("1.2".into())),
+            tok(TokenKind::Literal("-12.8".into())),
+            tok(TokenKind::RPar),
+            tok(TokenKind::LPar),
+            tok(TokenKind::Literal("*".into())),
+            tok(TokenKind::Literal("1.2".into())),
+            tok(TokenKind::Literal("-12.8".into())),
+            tok(TokenKind::RPar),
+            tok(TokenKind::RPar),
         ];
 
         let expected = Expr::list(&[
@@ -80,8 +115,431 @@ mod parse_tests {
         ]);
 
         parse(&tokens).map_or_else(
-            |err| assert!(false, format!("{:?}", err)),
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(expected, expr),
+        );
+    }
+
+    #[test]
+    fn unclosed_delimiter_points_at_opener() {
+        let tokens = lex("(+ 1\n  (* 2 3)\n  (- 4").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::BadParse(_, span)) => {
+                assert_eq!(Span::new(Pos::new(17, 3, 3), Pos::new(18, 3, 4)), span)
+            }
+            other => panic!("Expected an unclosed delimiter error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unexpected_rpar_points_at_rpar() {
+        let tokens = lex("\n   )").unwrap();
+        match parse(&tokens) {
+            Err(err) => {
+                assert_eq!(Pos::new(4, 2, 4), err.span().start);
+                assert_eq!("2:4: Unexpected ) encountered.", err.to_string());
+            }
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn eof_points_at_end_of_input() {
+        match parse(&[]) {
+            Err(ParseError::EOF(span)) => assert_eq!(Span::point(Pos::start()), span),
+            other => panic!("Expected EOF, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn datum_comments_skip_the_next_form() {
+        let tokens = lex("(+ 1 #;(* 2 3) 4 #;5)").unwrap();
+        let expected = Expr::list(&[Expr::symbol("+"), Expr::int(1), Expr::int(4)]);
+        parse(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(expected, expr),
+        );
+    }
+
+    #[test]
+    fn datum_comments_stack() {
+        let tokens = lex("#; #; a b c").unwrap();
+        parse(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(Expr::symbol("c"), expr),
+        );
+    }
+
+    #[test]
+    fn datum_comment_without_datum_is_an_error() {
+        let tokens = lex("(a #;)").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::BadParse(_, span)) => assert_eq!(Pos::new(5, 1, 6), span.start),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn quote_shorthands_expand_to_lists() {
+        let tokens = lex("('a `(b ,c ,@d))").unwrap();
+        let form = |name: &str, e| Expr::list(&[Expr::symbol(name), e]);
+        let expected = Expr::list(&[
+            form("quote", Expr::symbol("a")),
+            form(
+                "quasiquote",
+                Expr::list(&[
+                    Expr::symbol("b"),
+                    form("unquote", Expr::symbol("c")),
+                    form("unquote-splicing", Expr::symbol("d")),
+                ]),
+            ),
+        ]);
+        parse(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(expected, expr),
+        );
+    }
+
+    #[test]
+    fn quote_without_datum_is_an_error() {
+        match parse(&lex("'").unwrap()) {
+            Err(ParseError::EOF(span)) => assert_eq!(Pos::new(1, 1, 2), span.start),
+            other => panic!("Expected EOF, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_tokens_from_reader() {
+        let tokens = lex_reader("(+ 1 \"two\")".as_bytes()).unwrap();
+        let expected = Expr::list(&[Expr::symbol("+"), Expr::int(1), Expr::string("two")]);
+        parse(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
             |expr| assert_eq!(expected, expr),
         );
     }
+
+    #[test]
+    fn parse_vector_and_map_literals() {
+        let tokens = lex("{name \"mlisp\" tags [lisp toy] nested {}}").unwrap();
+        let expected = Expr::map(&[
+            (Expr::symbol("name"), Expr::string("mlisp")),
+            (
+                Expr::symbol("tags"),
+                Expr::vector(&[Expr::symbol("lisp"), Expr::symbol("toy")]),
+            ),
+            (Expr::symbol("nested"), Expr::map(&[])),
+        ]);
+        parse(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(expected, expr),
+        );
+    }
+
+    #[test]
+    fn mismatched_delimiters_are_reported() {
+        let tokens = lex("(a [b c)]").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::BadParse(msg, span)) => {
+                assert!(msg.contains("expected ] but found )"), "{}", msg);
+                assert_eq!(Pos::new(7, 1, 8), span.start);
+            }
+            other => panic!("Expected a mismatched delimiter error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unexpected_closing_bracket() {
+        match parse(&lex("]").unwrap()) {
+            Err(err) => assert_eq!("1:1: Unexpected ] encountered.", err.to_string()),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unclosed_brace_is_reported() {
+        match parse(&lex("{a 1").unwrap()) {
+            Err(ParseError::BadParse(msg, span)) => {
+                assert!(msg.contains("{ opened at 1:1"), "{}", msg);
+                assert_eq!(Pos::start(), span.start);
+            }
+            other => panic!("Expected an unclosed delimiter error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn map_literals_need_pairs_and_unique_keys() {
+        for input in &["{a 1 b}", "{a 1 a 2}"] {
+            match parse(&lex(input).unwrap()) {
+                Err(ParseError::BadParse(_, span)) => assert_eq!(Pos::start(), span.start),
+                other => panic!("Expected an error for {}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_numeric_literal_syntax() {
+        let tokens = lex("(#x1F #b1010 1_000 1/4 2.5e1 inf NaN +inf.0)").unwrap();
+        let expected = Expr::list(&[
+            Expr::int(31),
+            Expr::int(10),
+            Expr::int(1000),
+            Expr::rational(Rational::new(BigInt::from_i64(1), BigInt::from_i64(4)).unwrap()),
+            Expr::fnum(25.0),
+            Expr::symbol("inf"),
+            Expr::symbol("NaN"),
+            Expr::fnum(f64::INFINITY),
+        ]);
+        parse(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |expr| assert_eq!(expected, expr),
+        );
+    }
+
+    #[test]
+    fn malformed_number_tokens_are_parse_errors() {
+        match parse(&[tok(TokenKind::Literal("1/0".into()))]) {
+            Err(ParseError::BadParse(msg, _)) => assert!(msg.contains("1/0"), "{}", msg),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_program_reads_every_form() {
+        let tokens = lex("(let x 1) #;(skipped) x\n'y").unwrap();
+        let expected = vec![
+            Expr::list(&[Expr::symbol("let"), Expr::symbol("x"), Expr::int(1)]),
+            Expr::symbol("x"),
+            Expr::list(&[Expr::symbol("quote"), Expr::symbol("y")]),
+        ];
+        parse_program(&tokens).map_or_else(
+            |err| panic!("{:?}", err),
+            |forms| assert_eq!(expected, forms),
+        );
+    }
+
+    #[test]
+    fn parse_program_of_nothing_is_empty() {
+        let tokens = lex("; just a comment\n#;(skipped)").unwrap();
+        assert_eq!(Vec::<std::rc::Rc<Expr>>::new(), parse_program(&tokens).unwrap());
+    }
+
+    #[test]
+    fn parse_program_rejects_trailing_junk() {
+        let tokens = lex("(+ 1 2))").unwrap();
+        match parse_program(&tokens) {
+            Err(err) => assert_eq!("1:8: Unexpected ) encountered.", err.to_string()),
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn recovering_parser_reports_every_problem() {
+        let tokens = lex("(let x 1))\n(let y [2 3)\n(+ x\n(- x {a})\n(* 4 5)").unwrap();
+        let (forms, errors) = parse_program_recovering(&tokens);
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            vec![
+                "1:10: Unexpected ) encountered.",
+                "2:12: Mismatched delimiter: expected ] but found ) ([ opened at 2:8)",
+                "3:1: Unclosed delimiter: ( opened at 3:1 is never closed",
+                "4:6: Map literals must contain an even number of forms, as {key value ...}",
+            ],
+            messages
+        );
+        let num = Expr::int;
+        let expected = vec![
+            Expr::list(&[Expr::symbol("let"), Expr::symbol("x"), num(1)]),
+            Expr::list(&[Expr::symbol("let"), Expr::symbol("y"), Expr::vector(&[num(2), num(3)])]),
+            Expr::list(&[Expr::symbol("+"), Expr::symbol("x")]),
+            Expr::list(&[Expr::symbol("*"), num(4), num(5)]),
+        ];
+        assert_eq!(expected, forms);
+    }
+
+    #[test]
+    fn recovering_parser_drops_unmatched_closers() {
+        let tokens = lex("(a ] b)").unwrap();
+        let (forms, errors) = parse_program_recovering(&tokens);
+        assert_eq!(1, errors.len());
+        assert_eq!(Pos::new(3, 1, 4), errors[0].span().start);
+        assert_eq!(vec![Expr::list(&[Expr::symbol("a"), Expr::symbol("b")])], forms);
+    }
+
+    #[test]
+    fn recovering_parser_agrees_with_parse_program_on_valid_input() {
+        let tokens = lex("#;(ignored) (a [b {c d}]) 'e #; f").unwrap();
+        let (forms, errors) = parse_program_recovering(&tokens);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(parse_program(&tokens).unwrap(), forms);
+    }
+
+    #[test]
+    fn deeply_nested_input_does_not_overflow() {
+        let depth = 100_000;
+        let source = format!("{}x{}", "([".repeat(depth), "])".repeat(depth));
+        let mut expr = parse(&lex(&source).unwrap()).unwrap();
+        let mut levels = 0;
+        while let Expr::List(xs) | Expr::Vector(xs) = &*expr {
+            let inner = xs[0].clone();
+            expr = inner;
+            levels += 1;
+        }
+        assert_eq!(2 * depth, levels);
+        assert_eq!(Expr::symbol("x"), expr);
+    }
+
+    #[test]
+    fn depth_limit_is_a_parse_error() {
+        let options = ParseOptions { max_depth: Some(3) };
+        assert!(parse_with(&lex("(a '(b c))").unwrap(), options).is_ok());
+        match parse_with(&lex("(a '(b [c]))").unwrap(), options) {
+            Err(err) => assert_eq!("1:8: Nesting exceeds the maximum depth of 3", err.to_string()),
+            other => panic!("Expected a depth error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn check_input_complete() {
+        for input in &["", "  ; comment", "(+ 1 2)", "(let x 1) (print x) #;(skipped)", "\"a\" #| b |#"] {
+            assert!(matches!(check_input(input), InputStatus::Complete), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn check_input_incomplete() {
+        for input in &["(+ 1", "(let x [1 2", "{a (b", "'", "(a) #;", "(print \"hello", "#| open", "(a) \"b", r#"(print "\u{4"#, r#""\u"#, r#""\u{"#, "(char-upcase #\\", "#\\spa"] {
+            assert!(matches!(check_input(input), InputStatus::Incomplete), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn check_input_invalid() {
+        for input in &["(+ 1 2))", "(a ]", ") \"open", "(#x1G", "#\\bogus ", "(#\\bogus)"] {
+            match check_input(input) {
+                InputStatus::ParseError(_) | InputStatus::LexError(_) => {}
+                other => panic!("Expected {:?} to be invalid, got {:?}", input, other),
+            }
+        }
+        match check_input(r#"(print "\u{4")"#) {
+            InputStatus::LexError(LexError::InvalidEscape(text, _)) => assert_eq!("\\u{4", text),
+            other => panic!("Expected an invalid escape, got {:?}", other),
+        }
+        match check_input("(a {b} ") {
+            InputStatus::ParseError(err) => assert_eq!(Pos::new(3, 1, 4), err.span().start),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+    }
+
+    fn reparse(expr: &Rc<Expr>) -> Rc<Expr> {
+        let printed = expr.to_string();
+        let tokens = lex(&printed).unwrap_or_else(|err| panic!("{}: {}", printed, err));
+        parse(&tokens).unwrap_or_else(|err| panic!("{}: {}", printed, err))
+    }
+
+    #[test]
+    fn printed_exprs_read_back_identically() {
+        let exprs = vec![
+            Expr::symbol("hello-world!"),
+            Expr::fnum(3.2),
+            Expr::fnum(-42.0),
+            Expr::int(-42),
+            Expr::int(i64::MIN),
+            Expr::integer(BigInt::parse("-123456789012345678901234567890", 10).unwrap()),
+            Expr::rational(Rational::new(BigInt::from_i64(-1), BigInt::parse("123456789012345678901", 10).unwrap()).unwrap()),
+            Expr::fnum(0.1 + 0.2),
+            Expr::fnum(1e300),
+            Expr::fnum(-2.5e-12),
+            Expr::fnum(f64::INFINITY),
+            Expr::fnum(f64::NEG_INFINITY),
+            Expr::string("tab\tquote\" backslash\\ nul\0 bell\u{7} λ"),
+            Expr::list(&[]),
+            Expr::list(&[Expr::boolean(true), Expr::boolean(false)]),
+            Expr::map(&[
+                (Expr::symbol("k"), Expr::vector(&[Expr::fnum(1.0), Expr::string("")])),
+                (Expr::string("s"), Expr::list(&[Expr::symbol("quote"), Expr::symbol("x")])),
+            ]),
+        ];
+        for expr in &exprs {
+            assert_eq!(*expr, reparse(expr), "{}", expr);
+        }
+        let chars = [' ', '\n', '\t', '\r', '\0', '\u{7f}', '\u{1}', '\u{a0}', 'x', '(', ')', ';', '"', '\\', '#', 'λ'];
+        for c in chars {
+            let expr = Expr::character(c);
+            assert_eq!(expr, reparse(&expr), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn nan_reads_back_as_nan() {
+        match &*reparse(&Expr::fnum(f64::NAN)) {
+            Expr::FNum(n) => assert!(n.is_nan()),
+            other => panic!("Expected NaN, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn write_and_display_styles() {
+        let expr = Expr::list(&[Expr::string("a \"b\"\n"), Expr::character(' '), Expr::fnum(1.5), Expr::fnum(2.0), Expr::int(3)]);
+        assert_eq!("(\"a \\\"b\\\"\\n\" #\\space 1.5 2.0 3)", expr.to_string());
+        assert_eq!("(a \"b\"\n   1.5 2.0 3)", expr.printed(PrintStyle::Display).to_string());
+    }
+
+    #[test]
+    fn deeply_nested_exprs_print() {
+        let depth = 100_000;
+        let source = format!("{}x{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(source, parse(&lex(&source).unwrap()).unwrap().to_string());
+    }
+
+    #[test]
+    fn parse_dotted_pairs() {
+        let (a, b, c) = (Expr::symbol("a"), Expr::symbol("b"), Expr::symbol("c"));
+        let cases = [
+            ("(a . b)", Expr::dotted(std::slice::from_ref(&a), b.clone())),
+            ("(a b . c)", Expr::dotted(&[a.clone(), b.clone()], c.clone())),
+            ("(a . (b . c))", Expr::dotted(&[a.clone(), b.clone()], c.clone())),
+            ("(a . (b c))", Expr::list(&[a.clone(), b.clone(), c.clone()])),
+            ("(a . ())", Expr::list(std::slice::from_ref(&a))),
+            ("(a . 'b #;c)", Expr::dotted(std::slice::from_ref(&a), Expr::list(&[Expr::symbol("quote"), b.clone()]))),
+            ("((a . 1) (b . 2))", Expr::list(&[
+                Expr::dotted(std::slice::from_ref(&a), Expr::int(1)),
+                Expr::dotted(std::slice::from_ref(&b), Expr::int(2)),
+            ])),
+        ];
+        for (input, expected) in &cases {
+            assert_eq!(*expected, parse(&lex(input).unwrap()).unwrap(), "{}", input);
+        }
+        match &*parse(&lex("(a b . c)").unwrap()).unwrap() {
+            Expr::DottedList(xs, tail) => {
+                assert_eq!(2, xs.len());
+                assert_eq!(c, *tail);
+            }
+            other => panic!("Expected an improper list, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_dotted_lists_are_errors() {
+        let cases = [
+            ("(. a)", "1:2: Unexpected . encountered."),
+            ("(a .)", "1:4: Expected a datum after . in a dotted list"),
+            ("(a . b c)", "1:8: Only one datum may follow . in a dotted list"),
+            ("(a . b . c)", "1:8: Only one datum may follow . in a dotted list"),
+            ("[a . b]", "1:4: Unexpected . encountered."),
+            (".", "1:1: Unexpected . encountered."),
+        ];
+        for (input, expected) in &cases {
+            match parse(&lex(input).unwrap()) {
+                Err(err) => assert_eq!(*expected, err.to_string(), "{}", input),
+                other => panic!("Expected an error for {}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn dotted_lists_print_and_read_back() {
+        let expr = parse(&lex("(1 (a . b) [c] . \"d\")").unwrap()).unwrap();
+        assert_eq!("(1 (a . b) [c] . \"d\")", expr.to_string());
+        assert_eq!(expr, reparse(&expr));
+    }
 }